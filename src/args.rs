@@ -1,6 +1,10 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
 use std::path::PathBuf;
 
+pub use crate::key::Cipher;
+use crate::key::Padding;
+
 #[derive(Debug, Parser)]
 #[command(
     author,
@@ -11,6 +15,16 @@ use std::path::PathBuf;
 pub struct Cli {
     #[clap(subcommand)]
     pub subcommand: Option<PngKeyArgs>,
+    /// The output format of reports: print, decode, scan, batch summaries and the other commands.
+    #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
 }
 
 #[derive(Debug, Subcommand)]
@@ -19,41 +33,225 @@ pub enum PngKeyArgs {
     Decode(DecodeArgs),
     Remove(RemoveArgs),
     Print(PrintArgs),
+    Scan(ScanArgs),
+    Strip(StripArgs),
+    Vault(VaultArgs),
+    Rekey(RekeyArgs),
+    Seal(SealArgs),
+    CheckSeal(CheckSealArgs),
+    Keygen(KeygenArgs),
+    /// Manage the keyring of named key files.
+    Key(KeyArgs),
+    /// Split a secret into shares written into several images, any THRESHOLD of which recover it.
+    Split(SplitArgs),
+    /// Recover a secret from the shares held by several images.
+    Combine(CombineArgs),
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Default, Args)]
+#[command(allow_missing_positional = true)]
+#[command(group(ArgGroup::new("secret").args(["password", "key_file", "key"])))]
 pub struct EncodeArgs {
-    /// The file path to the Image file to be encoded.
+    /// The file path to the Image file to be encoded, or a directory or glob pattern.
     pub file_path: PathBuf,
-    /// The chunk type to be used for the message.
+    /// The chunk type to be used for the message. Omit it when using --name.
+    #[clap(required_unless_present = "name", default_value = "", hide_default_value = true)]
     pub chunk_type: String,
     /// The message to be encoded.
     pub message: String,
+    /// A format-independent slot name, used instead of CHUNK_TYPE in PNG, JPG and GIF alike.
+    #[clap(long, conflicts_with = "chunk_type")]
+    pub name: Option<String>,
     /// The output file path. If not specified, the original file will be overwritten.
     #[clap(short, long)]
     pub output: Option<PathBuf>,
     /// The password to be used for encryption. If not specified, the message will be stored in plain text.
     #[clap(short, long)]
     pub password: Option<String>,
+    /// A key file created by `keygen --symmetric`, used directly instead of a password.
+    #[clap(long)]
+    pub key_file: Option<PathBuf>,
+    /// The name of a key in the keyring, used directly instead of a password.
+    #[clap(long)]
+    pub key: Option<String>,
+    /// Overwrite the payload already stored in the chunk.
+    #[clap(long)]
+    pub force: bool,
+    /// The password of the payload already stored in the chunk. It is overwritten only when encrypted with this password.
+    #[clap(long, conflicts_with = "force")]
+    pub old_password: Option<String>,
+    /// Add the message as a new line after the payload already stored in the chunk.
+    #[clap(long, conflicts_with = "force")]
+    pub append: bool,
+    /// Also bind the encrypted payload to the image data, so it no longer decrypts once the pixels change.
+    #[clap(long, requires = "secret")]
+    pub bind_image: bool,
+    /// The cipher used to encrypt the message. Decoding picks it up from the payload.
+    #[clap(long, value_enum, default_value_t = Cipher::default(), requires = "secret")]
+    pub cipher: Cipher,
+    /// Pad the message before encryption so the payload size hides its length: bucket, random or a size in bytes.
+    #[clap(long, value_name = "bucket|random|N", requires = "secret")]
+    pub pad: Option<Padding>,
+    /// Spread the payload across FILE_PATH and these images, with Reed-Solomon parity so it survives losing some of them.
+    #[clap(long, num_args = 1.., value_name = "IMAGES", conflicts_with_all = ["append", "old_password", "bind_image", "output", "recursive"])]
+    pub spread: Vec<PathBuf>,
+    /// The number of images that may be lost when using --spread.
+    #[clap(long, default_value_t = 1, requires = "spread")]
+    pub parity: usize,
+    /// Append a new chunk by rewriting only the end of the original file instead of replacing it atomically. Faster for large images, but a crash mid-write can corrupt the file.
+    #[clap(long, conflicts_with_all = ["output", "output_dir", "spread"])]
+    pub in_place: bool,
+    /// Walk directories recursively.
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// The output directory for batch input, mirroring the input tree. If not specified, the original files will be overwritten.
+    #[clap(long, conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Default, Args)]
 pub struct DecodeArgs {
-    /// The file path to the Image file to be decoded.
+    /// The file path to the Image file to be decoded, or a directory or glob pattern.
     pub file_path: PathBuf,
-    /// The chunk type to be used for the message.
+    /// The chunk type to be used for the message. Omit it when using --name.
+    #[clap(required_unless_present = "name", default_value = "", hide_default_value = true)]
     pub chunk_type: String,
+    /// A format-independent slot name, used instead of CHUNK_TYPE in PNG, JPG and GIF alike.
+    #[clap(long, conflicts_with = "chunk_type")]
+    pub name: Option<String>,
     /// The password to be used for decryption. If not specified, will show the message in plain text.
     #[clap(short, long)]
     pub password: Option<String>,
+    /// A key file, or a directory of key files; the key is picked by the ID stored in the payload. Keys in the keyring are always tried.
+    #[clap(long)]
+    pub key_file: Option<PathBuf>,
+    /// Rebuild one payload spread by `encode --spread` across the images in FILE_PATH.
+    #[clap(long)]
+    pub spread: bool,
+    /// Walk directories recursively.
+    #[clap(short, long)]
+    pub recursive: bool,
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Default, Args)]
 pub struct RemoveArgs {
-    /// The file path to the Image file to be removed.
+    /// The file path to the Image file to be removed, or a directory or glob pattern.
     pub file_path: PathBuf,
-    /// The chunk type to be used for the message.
+    /// The chunk type to be used for the message. Omit it when using --name.
+    #[clap(required_unless_present = "name", default_value = "", hide_default_value = true)]
     pub chunk_type: String,
+    /// A format-independent slot name, used instead of CHUNK_TYPE in PNG, JPG and GIF alike.
+    #[clap(long, conflicts_with = "chunk_type")]
+    pub name: Option<String>,
+    /// The output file path. If not specified, the original file will be overwritten.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    /// Walk directories recursively.
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// The output directory for batch input, mirroring the input tree. If not specified, the original files will be overwritten.
+    #[clap(long, conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("new_secret").args(["new_password", "new_key_file", "new_key", "decrypt"])))]
+pub struct RekeyArgs {
+    /// The file path to the Image file to be re-encrypted, or a directory or glob pattern.
+    pub file_path: PathBuf,
+    /// The chunk type of the payload. Omit it when using --name.
+    #[clap(required_unless_present = "name", default_value = "", hide_default_value = true)]
+    pub chunk_type: String,
+    /// A format-independent slot name, used instead of CHUNK_TYPE in PNG, JPG and GIF alike.
+    #[clap(long, conflicts_with = "chunk_type")]
+    pub name: Option<String>,
+    /// The current password. Omit it if the payload is stored in plain text.
+    #[clap(long)]
+    pub old_password: Option<String>,
+    /// The current key file, or a directory of key files. Keys in the keyring are always tried.
+    #[clap(long)]
+    pub old_key_file: Option<PathBuf>,
+    /// The new password.
+    #[clap(long)]
+    pub new_password: Option<String>,
+    /// The new key file, used directly instead of a password.
+    #[clap(long)]
+    pub new_key_file: Option<PathBuf>,
+    /// The name of the new key in the keyring, used directly instead of a password.
+    #[clap(long)]
+    pub new_key: Option<String>,
+    /// Remove the encryption and store the payload in plain text.
+    #[clap(long)]
+    pub decrypt: bool,
+    /// The cipher used for the new key. If not specified, the payload keeps its current cipher.
+    #[clap(long, value_enum)]
+    pub cipher: Option<Cipher>,
+    /// Pad the message before encryption: bucket, random or a size in bytes. If not specified, a padded payload keeps its size.
+    #[clap(long, value_name = "bucket|random|N")]
+    pub pad: Option<Padding>,
+    /// The output file path. If not specified, the original file will be overwritten.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    /// Walk directories recursively.
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// The output directory for batch input, mirroring the input tree. If not specified, the original files will be overwritten.
+    #[clap(long, conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SealArgs {
+    /// The file path to the Image file to be sealed.
+    pub file_path: PathBuf,
+    /// The slot name of the seal.
+    #[clap(long, default_value = "seal")]
+    pub name: String,
+    /// Keep the seal in this chunk type instead of a named slot.
+    #[clap(long, conflicts_with = "name")]
+    pub chunk_type: Option<String>,
+    /// The password used to key the MAC of the seal.
+    #[clap(short, long)]
+    pub password: String,
+    /// The output file path. If not specified, the original file will be overwritten.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckSealArgs {
+    /// The file path to the Image file to be checked.
+    pub file_path: PathBuf,
+    /// The slot name of the seal.
+    #[clap(long, default_value = "seal")]
+    pub name: String,
+    /// Read the seal from this chunk type instead of a named slot.
+    #[clap(long, conflicts_with = "name")]
+    pub chunk_type: Option<String>,
+    /// The password used to key the MAC of the seal.
+    #[clap(short, long)]
+    pub password: String,
+}
+
+#[derive(Debug, Default, Clone, Args)]
+pub struct WriteArgs {
+    /// Keep a copy of the overwritten file as <FILE>.bak.
+    #[clap(long)]
+    pub backup: bool,
+    /// Keep the modification time of the original file.
+    #[clap(long)]
+    pub preserve_mtime: bool,
+    /// Show which chunks would be added, replaced or deleted without writing anything.
+    #[clap(long)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Args)]
@@ -65,4 +263,267 @@ pub struct PrintArgs {
     /// Show all chunks in the GIF file.
     #[clap(short, long)]
     pub all: bool,
-}
\ No newline at end of file
+    /// Show the offset, length and a hex dump of each chunk's data.
+    #[clap(long)]
+    pub hex: bool,
+    /// Dump at most N bytes of each chunk.
+    #[clap(long, value_name = "N", requires = "hex")]
+    pub limit: Option<usize>,
+}
+#[derive(Debug, Args)]
+pub struct ScanArgs {
+    /// The Image files, directories or glob patterns to be scanned for payloads.
+    #[clap(required = true)]
+    pub files: Vec<PathBuf>,
+    /// Walk directories recursively.
+    #[clap(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct KeygenArgs {
+    /// Generate a random 256-bit symmetric key file.
+    #[clap(long, required = true)]
+    pub symmetric: bool,
+    /// The key file to create. If not specified, the key file is printed to stdout.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct KeyArgs {
+    #[clap(subcommand)]
+    pub action: KeyAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum KeyAction {
+    /// Add a key to the keyring, generating a new one unless --from is given.
+    Add(KeyAddArgs),
+    /// List the names and IDs of all keys.
+    List,
+    /// Remove a key from the keyring.
+    Remove(KeyNameArgs),
+    /// Write a key out as a key file.
+    Export(KeyExportArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct KeyAddArgs {
+    /// The name of the key.
+    pub name: String,
+    /// Import this key file instead of generating a new key.
+    #[clap(long)]
+    pub from: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct KeyNameArgs {
+    /// The name of the key.
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct KeyExportArgs {
+    /// The name of the key.
+    pub name: String,
+    /// The key file to create. If not specified, the key file is printed to stdout.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("secret").args(["password", "key_file", "key"])))]
+pub struct SplitArgs {
+    /// The Image files receiving one share each, or glob patterns.
+    #[clap(required = true)]
+    pub files: Vec<PathBuf>,
+    /// The secret to be split.
+    #[clap(short, long)]
+    pub message: String,
+    /// The number of shares needed to recover the secret.
+    #[clap(long)]
+    pub threshold: u8,
+    /// The number of shares, one per image.
+    #[clap(long)]
+    pub shares: u8,
+    /// The slot name of the shares.
+    #[clap(long, default_value = "share")]
+    pub name: String,
+    /// Keep the shares in this chunk type instead of a named slot.
+    #[clap(long, conflicts_with = "name")]
+    pub chunk_type: Option<String>,
+    /// Encrypt the secret with this password before splitting it.
+    #[clap(short, long)]
+    pub password: Option<String>,
+    /// Encrypt the secret with this key file before splitting it.
+    #[clap(long)]
+    pub key_file: Option<PathBuf>,
+    /// Encrypt the secret with this key from the keyring before splitting it.
+    #[clap(long)]
+    pub key: Option<String>,
+    /// The cipher used to encrypt the secret.
+    #[clap(long, value_enum, default_value_t = Cipher::default(), requires = "secret")]
+    pub cipher: Cipher,
+    /// Pad the secret before encryption: bucket, random or a size in bytes.
+    #[clap(long, value_name = "bucket|random|N", requires = "secret")]
+    pub pad: Option<Padding>,
+    /// Overwrite payloads already stored in the chunk.
+    #[clap(long)]
+    pub force: bool,
+    /// The output directory. If not specified, the original files will be overwritten.
+    #[clap(long)]
+    pub output_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CombineArgs {
+    /// The Image files holding the shares, directories or glob patterns.
+    #[clap(required = true)]
+    pub files: Vec<PathBuf>,
+    /// The slot name of the shares.
+    #[clap(long, default_value = "share")]
+    pub name: String,
+    /// The chunk type of the shares, if they are not in a named slot.
+    #[clap(long, conflicts_with = "name")]
+    pub chunk_type: Option<String>,
+    /// The password of the secret, if it was encrypted.
+    #[clap(short, long)]
+    pub password: Option<String>,
+    /// A key file, or a directory of key files. Keys in the keyring are always tried.
+    #[clap(long)]
+    pub key_file: Option<PathBuf>,
+    /// Walk directories recursively.
+    #[clap(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StripCategory {
+    /// EXIF, including GPS.
+    Exif,
+    /// The EXIF orientation tag, kept as a minimal EXIF when EXIF is dropped.
+    Orientation,
+    Xmp,
+    Icc,
+    /// Comments and text chunks.
+    Comment,
+    /// Gamma, chromaticity, sRGB and other colour hints.
+    Color,
+    /// Modification time.
+    Time,
+    /// Physical pixel size and image offset.
+    Physical,
+    /// Private and unknown chunks, including pngkey payloads.
+    Private,
+    /// Other non-essential metadata such as thumbnails and Photoshop blocks.
+    Other,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct StripArgs {
+    /// The file path to the Image file to be stripped, or a directory or glob pattern.
+    pub file_path: PathBuf,
+    /// Keep only these categories and drop the rest.
+    #[clap(long, value_enum, value_delimiter = ',', conflicts_with = "drop")]
+    pub keep: Vec<StripCategory>,
+    /// Drop only these categories and keep the rest.
+    #[clap(long, value_enum, value_delimiter = ',')]
+    pub drop: Vec<StripCategory>,
+    /// The output file path. If not specified, the original file will be overwritten.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    /// Walk directories recursively.
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// The output directory for batch input, mirroring the input tree. If not specified, the original files will be overwritten.
+    #[clap(long, conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct VaultArgs {
+    #[clap(subcommand)]
+    pub action: VaultAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VaultAction {
+    /// Add or replace an entry.
+    Set(VaultSetArgs),
+    /// Show the value of an entry.
+    Get(VaultGetArgs),
+    /// List the keys of all entries.
+    List(VaultListArgs),
+    /// Delete an entry.
+    Delete(VaultDeleteArgs),
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("vault_secret").args(["password", "key_file", "keyring_key"])))]
+pub struct VaultFileArgs {
+    /// The file path to the Image file holding the vault.
+    pub file_path: PathBuf,
+    /// The slot name of the vault.
+    #[clap(long, default_value = "vault")]
+    pub name: String,
+    /// Keep the vault in this chunk type instead of a named slot.
+    #[clap(long, conflicts_with = "name")]
+    pub chunk_type: Option<String>,
+    /// The password of the vault. If no password, key file or key is specified, the vault is stored in plain text.
+    #[clap(short, long)]
+    pub password: Option<String>,
+    /// A key file created by `keygen --symmetric`, used directly instead of a password.
+    #[clap(long)]
+    pub key_file: Option<PathBuf>,
+    /// The name of a key in the keyring, used directly instead of a password.
+    #[clap(long = "key", value_name = "KEY")]
+    pub keyring_key: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct VaultSetArgs {
+    #[clap(flatten)]
+    pub vault: VaultFileArgs,
+    /// The key of the entry.
+    pub key: String,
+    /// The value of the entry.
+    pub value: String,
+    /// The output file path. If not specified, the original file will be overwritten.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct VaultGetArgs {
+    #[clap(flatten)]
+    pub vault: VaultFileArgs,
+    /// The key of the entry.
+    pub key: String,
+}
+
+#[derive(Debug, Args)]
+pub struct VaultListArgs {
+    #[clap(flatten)]
+    pub vault: VaultFileArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct VaultDeleteArgs {
+    #[clap(flatten)]
+    pub vault: VaultFileArgs,
+    /// The key of the entry.
+    pub key: String,
+    /// The output file path. If not specified, the original file will be overwritten.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    #[clap(flatten)]
+    pub write: WriteArgs,
+}
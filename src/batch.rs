@@ -0,0 +1,133 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::Result;
+
+/// 批量处理中的一个输入文件
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub path: PathBuf,
+    /// 相对于输入根目录的路径，用于在输出目录中重建目录结构
+    pub relative: PathBuf,
+}
+
+/// 遍历目录时处理的文件扩展名
+static EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "gif"];
+
+fn has_wildcard(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// `-`表示标准输入或标准输出
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// 是否为批量输入，即目录或通配符
+pub fn is_batch(path: &Path) -> bool {
+    path.is_dir() || (!path.exists() && has_wildcard(path))
+}
+
+/// 通配符之前的目录部分
+fn glob_base(pattern: &Path) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in pattern.components() {
+        if has_wildcard(Path::new(&component)) {
+            break;
+        }
+        base.push(component);
+    }
+    base
+}
+
+fn walk(dir: &Path, root: &Path, recursive: bool, inputs: &mut Vec<Input>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if recursive {
+                walk(&path, root, recursive, inputs)?;
+            }
+        } else if is_image(&path) {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            inputs.push(Input { path, relative });
+        }
+    }
+    Ok(())
+}
+
+/// 展开文件、目录或通配符为输入文件列表
+pub fn collect(path: &Path, recursive: bool) -> Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    if path.is_dir() {
+        walk(path, path, recursive, &mut inputs)?;
+    } else if is_batch(path) {
+        let base = glob_base(path);
+        for entry in glob::glob(&path.to_string_lossy())? {
+            let entry = entry?;
+            if entry.is_dir() {
+                walk(&entry, &base, recursive, &mut inputs)?;
+            } else {
+                let relative = entry.strip_prefix(&base).unwrap_or(&entry).to_path_buf();
+                inputs.push(Input { path: entry, relative });
+            }
+        }
+    } else {
+        let relative = PathBuf::from(path.file_name().unwrap_or(path.as_os_str()));
+        inputs.push(Input { path: path.to_path_buf(), relative });
+    }
+    Ok(inputs)
+}
+
+/// 输出路径：指定输出目录时按相对路径重建目录结构，否则覆写原文件
+pub fn output_path(input: &Input, output_dir: Option<&Path>) -> PathBuf {
+    match output_dir {
+        Some(output_dir) => output_dir.join(&input.relative),
+        None => input.path.clone(),
+    }
+}
+
+/// 在所有CPU核心上并行处理输入，结果与输入顺序一致
+pub fn run<T, F>(inputs: &[Input], f: F) -> Vec<std::result::Result<T, String>>
+where
+    T: Send,
+    F: Fn(&Input) -> Result<T> + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(inputs.len())
+        .max(1);
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..inputs.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(index) else {
+                    break;
+                };
+                let result = f(input).map_err(|e| e.to_string());
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err("Not processed".to_owned())))
+        .collect()
+}
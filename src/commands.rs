@@ -1,6 +1,10 @@
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use crate::args::*;
+use crate::batch::{self, Input};
 use crate::png::is_png;
 use crate::png::command as pngcommand;
 use crate::jpg::is_jpg;
@@ -8,80 +12,1477 @@ use crate::jpg::command as jpgcommand;
 use crate::gif::is_gif;
 use crate::gif::command as gifcommand;
 use crate::Result;
+use crate::verify::{self, Segment};
+use crate::slot;
+use crate::source;
+use crate::write;
+use crate::stream::{self, Rewrite};
+use crate::key::{self, Context, DerivedKey, KeyCache, PayloadInfo};
+use crate::keyfile::{self, KeyFile};
+use crate::keyring;
+use crate::report::{self, BatchReport, ChangeReport, CombineReport, DecodeReport, FileReport, KeyReport, KeygenReport, ScanReport, SealPartReport, SealReport, SlotReport, VaultEntryReport, VaultListReport, WriteReport};
+use crate::seal::{Part, Seal, Status};
+use crate::share::{self, Share};
+use crate::spread::{self, Fragment};
+use crate::vault::Vault;
+use sha2::{Digest, Sha256};
 
-pub fn encode(args: EncodeArgs) -> Result<()> {
-    if !args.file_path.exists() {
+fn read_file(file_path: &Path) -> Result<Vec<u8>> {
+    if batch::is_stdio(file_path) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+    if !file_path.exists() {
         return Err("File does not exist".into());
     }
-    let bytes = fs::read(args.file_path.clone())?;
+    Ok(fs::read(file_path)?)
+}
 
-    if is_png(&bytes) {
-        pngcommand::encode(args, &bytes)?;
+fn segments(bytes: &[u8]) -> Result<Vec<Segment>> {
+    if is_png(bytes) {
+        pngcommand::segments(bytes)
+    } else if is_jpg(bytes) {
+        jpgcommand::segments(bytes)
+    } else if is_gif(bytes) {
+        gifcommand::segments(bytes)
+    } else {
+        Err("No Supported Format".into())
+    }
+}
+
+/// 读出载荷的原始字节
+fn decode_payload(args: &DecodeArgs, bytes: &[u8]) -> Result<(&'static str, Vec<u8>)> {
+    if is_png(bytes) {
+        Ok(("png", pngcommand::decode(args, bytes)?))
+    } else if is_jpg(bytes) {
+        Ok(("jpg", jpgcommand::decode(args, bytes)?))
+    } else if is_gif(bytes) {
+        Ok(("gif", gifcommand::decode(args, bytes)?))
+    } else {
+        Err("No Supported Format".into())
+    }
+}
+
+fn format_name(bytes: &[u8]) -> Result<&'static str> {
+    if is_png(bytes) {
+        Ok("png")
+    } else if is_jpg(bytes) {
+        Ok("jpg")
+    } else if is_gif(bytes) {
+        Ok("gif")
+    } else {
+        Err("No Supported Format".into())
+    }
+}
+
+/// 图像数据的SHA-256：PNG的IDAT、JPG的扫描数据与GIF的图像块，不受元数据块增删的影响
+fn image_digest(bytes: &[u8]) -> Result<[u8; 32]> {
+    let parts = if is_png(bytes) {
+        pngcommand::pixel_data(bytes)?
+    } else if is_jpg(bytes) {
+        jpgcommand::pixel_data(bytes)?
+    } else if is_gif(bytes) {
+        gifcommand::pixel_data(bytes)?
+    } else {
+        return Err("No Supported Format".into());
+    };
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// 载荷所在的位置，作为加密的关联数据；`bind_image`时还包括图像数据的摘要
+fn payload_context<'a>(bytes: &[u8], chunk_type: &'a str, bind_image: bool) -> Result<Context<'a>> {
+    Ok(Context {
+        format: format_name(bytes)?,
+        chunk_type,
+        image: bind_image.then(|| image_digest(bytes)).transpose()?,
+    })
+}
+
+/// 加密使用的密钥：密码经Argon2id派生，密钥文件与密钥环中的密钥直接使用
+fn encryption_key(password: Option<&str>, key_file: Option<&Path>, key: Option<&str>) -> Result<Option<DerivedKey>> {
+    Ok(match (password, key_file, key) {
+        (_, Some(key_file), _) => Some(keyfile::read(key_file)?),
+        (_, _, Some(name)) => Some(keyring::get(name)?.key()?),
+        (Some(password), None, None) if !password.is_empty() => Some(DerivedKey::generate(password)?),
+        _ => None,
+    })
+}
+
+/// 解密使用的密钥：密码、指定的密钥文件以及密钥环中的密钥，记录了密钥ID的载荷按ID选用
+fn decryption_keys(password: Option<&str>, key_file: Option<&Path>) -> Result<KeyCache> {
+    Ok(KeyCache::new(password.unwrap_or_default())
+        .with_key_files(key_file.map(keyfile::load).transpose()?.unwrap_or_default())
+        .with_key_files(keyring::keys()))
+}
+
+/// 解密载荷，关联数据取自载荷所在的文件与块
+fn decrypt_payload(keys: &KeyCache, bytes: &[u8], chunk_type: &str, payload: &[u8]) -> Result<String> {
+    let message = std::str::from_utf8(payload).map_err(|_| "Invalid UTF-8")?;
+    let context = payload_context(bytes, chunk_type, key::binds_image(message))?;
+    keys.decrypt(message, &context)
+}
+
+/// 写入前重新解析输出：除载荷所在块外其余块必须与原文件相同，
+/// 编码时还需能读出刚写入的载荷。返回载荷所在块的改动
+fn verify_output(bytes: &[u8], result: &[u8], chunk_type: &str, payload: Option<&[u8]>) -> Result<Vec<ChangeReport>> {
+    let (before, after) = (segments(bytes)?, segments(result)?);
+    verify::verify_chunks(&before, &after, Some((bytes, result)), chunk_type)?;
+    if let Some(payload) = payload {
+        let args = DecodeArgs {
+            chunk_type: chunk_type.to_owned(),
+            ..Default::default()
+        };
+        let (_, written) = decode_payload(&args, result)?;
+        verify::verify_payload(&written, payload)?;
+    }
+    Ok(verify::changes(&before, &after, chunk_type))
+}
+
+/// 写入已校验的输出，dry-run时只返回改动
+fn write_output(
+    file_path: &Path,
+    output: &Path,
+    bytes: &[u8],
+    result: &[u8],
+    changes: Vec<ChangeReport>,
+    options: &WriteArgs,
+) -> Result<WriteReport> {
+    if !options.dry_run {
+        if batch::is_stdio(output) {
+            write::write_stdout(result)?;
+        } else {
+            write::write_atomic(file_path, output, result, options)?;
+        }
+    }
+    Ok(WriteReport {
+        file: file_path.display().to_string(),
+        output: output.display().to_string(),
+        dry_run: options.dry_run,
+        original_size: bytes.len(),
+        size: result.len(),
+        changes,
+    })
+}
+
+/// 可以流式处理的格式
+#[derive(Debug, Clone, Copy)]
+enum Streamable {
+    Png,
+    Jpg,
+}
+
+/// 输入为PNG或JPG文件时流式处理，标准输入与GIF读入内存处理
+fn streamable(file_path: &Path) -> Result<Option<Streamable>> {
+    if batch::is_stdio(file_path) {
+        return Ok(None);
+    }
+    if !file_path.exists() {
+        return Err("File does not exist".into());
+    }
+    let mut head = [0u8; 8];
+    let n = stream::read_full(&mut File::open(file_path)?, &mut head)?;
+    if is_png(&head[..n]) {
+        Ok(Some(Streamable::Png))
+    } else if is_jpg(&head[..n]) {
+        Ok(Some(Streamable::Jpg))
+    } else {
+        Ok(None)
+    }
+}
+
+fn segments_stream<R: BufRead>(format: Streamable, reader: R, chunk_type: &str) -> Result<(Vec<Segment>, Option<Vec<u8>>)> {
+    match format {
+        Streamable::Png => pngcommand::segments_stream(reader, chunk_type),
+        Streamable::Jpg => jpgcommand::segments_stream(reader, chunk_type),
+    }
+}
+
+/// 流式写入：边读边写入临时文件，再流式读回临时文件校验，只有载荷块读入内存
+fn stream_file<F>(
+    format: Streamable,
+    file_path: &Path,
+    output: &Path,
+    chunk_type: &str,
+    payload: Option<&[u8]>,
+    options: &WriteArgs,
+    rewrite: F,
+) -> Result<WriteReport>
+where
+    F: FnOnce(BufReader<File>, &mut dyn Write) -> Result<Rewrite>,
+{
+    let reader = BufReader::new(File::open(file_path)?);
+    let result = if options.dry_run {
+        let result = rewrite(reader, &mut io::sink())?;
+        verify::verify_chunks(&result.before, &result.after, None, chunk_type)?;
+        result
+    } else {
+        write::write_atomic_with(file_path, output, options, |file| {
+            let mut writer = BufWriter::new(&mut *file);
+            let result = rewrite(reader, &mut writer)?;
+            writer.flush()?;
+            drop(writer);
+
+            file.seek(SeekFrom::Start(0))?;
+            let (after, written) = segments_stream(format, BufReader::new(&mut *file), chunk_type)?;
+            verify::verify_chunks(&result.before, &after, None, chunk_type)?;
+            if let Some(payload) = payload {
+                verify::verify_payload(&written.ok_or("Chunk not found")?, payload)?;
+            }
+            Ok(Rewrite { after, ..result })
+        })?
+    };
+
+    Ok(WriteReport {
+        file: file_path.display().to_string(),
+        output: output.display().to_string(),
+        dry_run: options.dry_run,
+        original_size: result.original_size,
+        size: result.size,
+        changes: verify::changes(&result.before, &result.after, chunk_type),
+    })
+}
+
+/// 单个文件只在dry-run时输出改动
+fn show_write(report: WriteReport, format: OutputFormat) -> Result<()> {
+    if !report.dry_run {
+        return Ok(());
+    }
+    if format == OutputFormat::Text {
+        report.print();
+        return Ok(());
+    }
+    report::emit(&report, format)
+}
+
+/// 批量写入的汇总，dry-run时先列出每个文件的改动
+fn summarize_writes(summary: BatchReport<WriteReport>, format: OutputFormat, show_changes: bool) -> Result<()> {
+    if show_changes && format == OutputFormat::Text {
+        for result in &summary.results {
+            result.print();
+        }
+    }
+    summarize(summary, format)
+}
+
+/// 指定--in-place、覆写原文件且新块可以放在结尾标记之前时原地追加，只写入新块与结尾标记。
+/// 未指定--in-place、需要备份、dry-run或无法原地追加时返回None，由调用方完整重写
+fn append_file(args: &EncodeArgs, file_path: &Path, output: &Path, payload: &[u8]) -> Result<Option<WriteReport>> {
+    let options = &args.write;
+    if !args.in_place || options.dry_run || options.backup || batch::is_stdio(file_path) || output != file_path {
+        return Ok(None);
+    }
+
+    let append = match streamable(file_path)? {
+        Some(Streamable::Png) => pngcommand::append(args, &File::open(file_path)?, payload)?,
+        Some(Streamable::Jpg) => None,
+        None => {
+            let bytes = source::open(file_path)?;
+            if is_gif(&bytes) { gifcommand::append(args, &bytes, payload)? } else { None }
+        }
+    };
+    let Some(append) = append else {
+        return Ok(None);
+    };
+
+    // 与其他写入方式一样，写入后重新解码载荷并比对
+    write::append_in_place(file_path, &append, options, || {
+        let decode_args = DecodeArgs {
+            chunk_type: args.chunk_type.clone(),
+            ..Default::default()
+        };
+        let (_, written) = decode_payload(&decode_args, &source::open(file_path)?)?;
+        verify::verify_payload(&written, payload)
+    })?;
+    let size = append.offset as usize + append.tail.len();
+    Ok(Some(WriteReport {
+        file: file_path.display().to_string(),
+        output: output.display().to_string(),
+        dry_run: false,
+        original_size: size - append.length,
+        size,
+        changes: vec![ChangeReport {
+            action: "added",
+            chunk_type: args.chunk_type.clone(),
+            offset: append.offset as usize,
+            length: append.length,
+            category: None,
+        }],
+    }))
+}
+
+/// 目标块已有载荷时默认拒绝覆写：指定--force，或已有载荷用--old-password加密时才覆写；
+/// 指定--append时解密已有的信息，新信息作为新的一行追加在后面。返回要写入的信息
+fn message_to_write(args: &EncodeArgs, bytes: &[u8], keys: &KeyCache) -> Result<String> {
+    if args.force || !segments(bytes)?.iter().any(|segment| segment.chunk_type == args.chunk_type) {
+        return Ok(args.message.clone());
+    }
+    if !args.append && args.old_password.is_none() {
+        return Err(format!(
+            "Chunk {} already holds a payload, use --force or --old-password to overwrite it, or --append to add to it",
+            args.chunk_type
+        ).into());
+    }
+
+    let decode_args = DecodeArgs {
+        chunk_type: args.chunk_type.clone(),
+        ..Default::default()
+    };
+    let (_, payload) = decode_payload(&decode_args, bytes)?;
+    if args.append {
+        let existing = decrypt_payload(keys, bytes, &args.chunk_type, &payload)?;
+        return Ok(format!("{}\n{}", existing, args.message));
+    }
+
+    // 只用--old-password解密：明文载荷、密钥文件加密的载荷或密码不对时都不能覆写
+    let old_password = args.old_password.as_deref().unwrap_or_default();
+    let encrypted = std::str::from_utf8(&payload).ok().and_then(key::salt).is_some();
+    if !encrypted || decrypt_payload(&KeyCache::new(old_password), bytes, &args.chunk_type, &payload).is_err() {
+        return Err(format!(
+            "The payload in chunk {} is not encrypted with the old password, use --force to overwrite it",
+            args.chunk_type
+        ).into());
+    }
+    Ok(args.message.clone())
+}
+
+fn encode_file(args: &EncodeArgs, file_path: &Path, output: &Path, key: Option<&DerivedKey>, keys: &KeyCache) -> Result<WriteReport> {
+    // 标准输入只能读取一次，先读入内存
+    let stdin = batch::is_stdio(file_path).then(|| read_file(file_path)).transpose()?;
+    let source = match stdin {
+        Some(_) => None,
+        None => Some(source::open(file_path)?),
+    };
+    let bytes = stdin.as_deref().or(source.as_deref()).unwrap_or_default();
+    let message = message_to_write(args, bytes, keys)?;
+    let payload = match key {
+        Some(key) => key::encrypt_with(&message, key, args.cipher, args.pad, &payload_context(bytes, &args.chunk_type, args.bind_image)?)?,
+        None => message,
+    };
+    drop(source);
+
+    if let Some(report) = append_file(args, file_path, output, payload.as_bytes())? {
+        return Ok(report);
+    }
+
+    if let Some(format) = streamable(file_path)?.filter(|_| !batch::is_stdio(output)) {
+        let payload = payload.as_bytes();
+        return stream_file(format, file_path, output, &args.chunk_type, Some(payload), &args.write, |reader, writer| {
+            match format {
+                Streamable::Png => pngcommand::encode_stream(args, reader, writer, payload),
+                Streamable::Jpg => jpgcommand::encode_stream(args, reader, writer, payload),
+            }
+        });
+    }
+
+    let bytes = match stdin {
+        Some(bytes) => bytes,
+        None => read_file(file_path)?,
+    };
+
+    let result = if is_png(&bytes) {
+        pngcommand::encode(args, &bytes, payload.as_bytes())?
     } else if is_jpg(&bytes) {
-        jpgcommand::encode(args, &bytes)?;
+        jpgcommand::encode(args, &bytes, payload.as_bytes())?
     } else if is_gif(&bytes) {
-        gifcommand::encode(args, &bytes)?;
+        gifcommand::encode(args, &bytes, payload.as_bytes())?
     } else {
         return Err("No Supported Format".into());
-    }
+    };
 
-    Ok(())
+    let changes = verify_output(&bytes, &result, &args.chunk_type, Some(payload.as_bytes()))?;
+    write_output(file_path, output, &bytes, &result, changes, &args.write)
 }
 
-pub fn decode(args: DecodeArgs) -> Result<String> {
-    if !args.file_path.exists() {
+pub fn encode(mut args: EncodeArgs, format: OutputFormat) -> Result<()> {
+    args.chunk_type = slot::resolve(&args.chunk_type, args.name.as_deref())?;
+    if !args.spread.is_empty() {
+        return encode_spread(args, format);
+    }
+    let inputs = batch::collect(&args.file_path, args.recursive)?;
+    if !batch::is_batch(&args.file_path) && !batch::is_stdio(&args.file_path) && !args.file_path.exists() {
         return Err("File does not exist".into());
     }
-    let bytes = fs::read(args.file_path.clone())?;
 
-    let result: String;
-    if is_png(&bytes) {
-        result = pngcommand::decode(args, &bytes)?;
+    // 整个批次只派生一次密钥
+    let key = encryption_key(args.password.as_deref(), args.key_file.as_deref(), args.key.as_deref())?;
+    // 解密已有的载荷，--append未指定旧密码时使用新密码、密钥文件或密钥环
+    let keys = decryption_keys(args.old_password.as_deref().or(args.password.as_deref()), args.key_file.as_deref())?;
+
+    if !batch::is_batch(&args.file_path) {
+        let output = match &args.output {
+            Some(output) => output.clone(),
+            None => batch::output_path(&inputs[0], args.output_dir.as_deref()),
+        };
+        let report = encode_file(&args, &args.file_path, &output, key.as_ref(), &keys)?;
+        return show_write(report, format);
+    }
+    if args.output.is_some() {
+        return Err("--output only applies to a single file, use --output-dir instead".into());
+    }
+
+    let results = batch::run(&inputs, |input| {
+        let output = batch::output_path(input, args.output_dir.as_deref());
+        encode_file(&args, &input.path, &output, key.as_ref(), &keys)
+    });
+    summarize_writes(BatchReport::new(&inputs, results), format, args.write.dry_run)
+}
+
+fn decode_file(args: &DecodeArgs, file_path: &Path, keys: &KeyCache) -> Result<DecodeReport> {
+    let bytes = source::open(file_path)?;
+    let (file_format, payload) = decode_payload(args, &bytes)?;
+
+    Ok(DecodeReport {
+        file: file_path.display().to_string(),
+        format: file_format,
+        chunk_type: args.chunk_type.clone(),
+        payload: key::inspect(&payload),
+        message: decrypt_payload(keys, &bytes, &args.chunk_type, &payload)?,
+    })
+}
+
+/// 返回解码后的信息，批量模式下返回空字符串
+pub fn decode(mut args: DecodeArgs, format: OutputFormat) -> Result<String> {
+    args.chunk_type = slot::resolve(&args.chunk_type, args.name.as_deref())?;
+    // 相同salt的载荷只派生一次密钥
+    let keys = decryption_keys(args.password.as_deref(), args.key_file.as_deref())?;
+
+    if args.spread || !batch::is_batch(&args.file_path) {
+        let result = match args.spread {
+            true => decode_spread(&args, &keys)?,
+            false => decode_file(&args, &args.file_path, &keys)?,
+        };
+        if format == OutputFormat::Text {
+            println!("{}", result.message);
+        } else {
+            report::emit(&result, format)?;
+        }
+        return Ok(result.message);
+    }
+
+    let inputs = batch::collect(&args.file_path, args.recursive)?;
+    let results = batch::run(&inputs, |input| decode_file(&args, &input.path, &keys));
+    let summary = BatchReport::new(&inputs, results);
+    if format == OutputFormat::Text {
+        for result in &summary.results {
+            println!("{}: {}", result.file, result.message);
+        }
+    }
+    summarize(summary, format)?;
+    Ok(String::new())
+}
+
+fn remove_file(args: &RemoveArgs, file_path: &Path, output: &Path) -> Result<WriteReport> {
+    if let Some(format) = streamable(file_path)?.filter(|_| !batch::is_stdio(output)) {
+        return stream_file(format, file_path, output, &args.chunk_type, None, &args.write, |reader, writer| {
+            match format {
+                Streamable::Png => pngcommand::remove_stream(args, reader, writer),
+                Streamable::Jpg => jpgcommand::remove_stream(args, reader, writer),
+            }
+        });
+    }
+
+    let bytes = read_file(file_path)?;
+
+    let result = if is_png(&bytes) {
+        pngcommand::remove(args, &bytes)?
     } else if is_jpg(&bytes) {
-        result = jpgcommand::decode(args, &bytes)?;
+        jpgcommand::remove(args, &bytes)?
     } else if is_gif(&bytes) {
-        result = gifcommand::decode(args, &bytes)?;
+        gifcommand::remove(args, &bytes)?
     } else {
         return Err("No Supported Format".into());
+    };
+
+    let changes = verify_output(&bytes, &result, &args.chunk_type, None)?;
+    write_output(file_path, output, &bytes, &result, changes, &args.write)
+}
+
+pub fn remove(mut args: RemoveArgs, format: OutputFormat) -> Result<()> {
+    args.chunk_type = slot::resolve(&args.chunk_type, args.name.as_deref())?;
+    let inputs = batch::collect(&args.file_path, args.recursive)?;
+    if !batch::is_batch(&args.file_path) {
+        let output = match &args.output {
+            Some(output) => output.clone(),
+            None => batch::output_path(&inputs[0], args.output_dir.as_deref()),
+        };
+        let report = remove_file(&args, &args.file_path, &output)?;
+        return show_write(report, format);
+    }
+    if args.output.is_some() {
+        return Err("--output only applies to a single file, use --output-dir instead".into());
     }
-    
-    Ok(result)
+
+    let results = batch::run(&inputs, |input| {
+        let output = batch::output_path(input, args.output_dir.as_deref());
+        remove_file(&args, &input.path, &output)
+    });
+    summarize_writes(BatchReport::new(&inputs, results), format, args.write.dry_run)
 }
 
-pub fn remove(args: RemoveArgs) -> Result<()> {
-    if !args.file_path.exists() {
-        return Err("File does not exist".into());
+/// 在内存中用旧密码解密、新密码重新加密，明文不输出，只改写载荷所在的块
+fn rekey_file(args: &RekeyArgs, file_path: &Path, output: &Path, keys: &KeyCache, key: Option<&DerivedKey>) -> Result<WriteReport> {
+    let decode_args = DecodeArgs {
+        chunk_type: args.chunk_type.clone(),
+        ..Default::default()
+    };
+    // 写入前释放文件映射，替换原文件时不能仍映射着它
+    let encode_args = {
+        let bytes = source::open(file_path)?;
+        let (_, payload) = decode_payload(&decode_args, &bytes)?;
+        EncodeArgs {
+            file_path: file_path.to_path_buf(),
+            chunk_type: args.chunk_type.clone(),
+            message: decrypt_payload(keys, &bytes, &args.chunk_type, &payload)?,
+            force: true,
+            // 保留原来的绑定范围
+            bind_image: std::str::from_utf8(&payload).is_ok_and(key::binds_image),
+            cipher: args.cipher.or_else(|| std::str::from_utf8(&payload).ok().and_then(key::cipher)).unwrap_or_default(),
+            pad: args.pad.or_else(|| std::str::from_utf8(&payload).ok().and_then(key::padding)),
+            write: args.write.clone(),
+            ..Default::default()
+        }
+    };
+    encode_file(&encode_args, file_path, output, key, keys)
+}
+
+/// 更换载荷的密码，也可以为明文载荷加密，或用--decrypt去掉加密
+pub fn rekey(mut args: RekeyArgs, format: OutputFormat) -> Result<()> {
+    args.chunk_type = slot::resolve(&args.chunk_type, args.name.as_deref())?;
+    // 去掉加密会以明文写回，必须用--decrypt显式指定
+    let has_new = args.new_password.as_deref().is_some_and(|password| !password.is_empty())
+        || args.new_key_file.is_some()
+        || args.new_key.is_some();
+    if !has_new && !args.decrypt {
+        return Err("No new secret given, specify --new-password, --new-key-file or --new-key, or --decrypt to store the payload in plain text".into());
     }
-    let bytes = fs::read(args.file_path.clone())?;
-    
-    if is_png(&bytes) {
-        pngcommand::remove(args, &bytes)?;
+    if batch::is_stdio(&args.file_path) {
+        return Err("Rekey does not support reading from stdin".into());
+    }
+
+    // 旧密钥与新密钥的来源与decode和encode相同，整个批次只派生一次新密钥
+    let keys = decryption_keys(args.old_password.as_deref(), args.old_key_file.as_deref())?;
+    let key = encryption_key(args.new_password.as_deref(), args.new_key_file.as_deref(), args.new_key.as_deref())?;
+
+    let inputs = batch::collect(&args.file_path, args.recursive)?;
+    if !batch::is_batch(&args.file_path) {
+        let output = match &args.output {
+            Some(output) => output.clone(),
+            None => batch::output_path(&inputs[0], args.output_dir.as_deref()),
+        };
+        let report = rekey_file(&args, &args.file_path, &output, &keys, key.as_ref())?;
+        return show_write(report, format);
+    }
+    if args.output.is_some() {
+        return Err("--output only applies to a single file, use --output-dir instead".into());
+    }
+
+    let results = batch::run(&inputs, |input| {
+        let output = batch::output_path(input, args.output_dir.as_deref());
+        rekey_file(&args, &input.path, &output, &keys, key.as_ref())
+    });
+    summarize_writes(BatchReport::new(&inputs, results), format, args.write.dry_run)
+}
+
+fn strip_file(args: &StripArgs, file_path: &Path, output: &Path) -> Result<WriteReport> {
+    let bytes = read_file(file_path)?;
+
+    let (result, stripped) = if is_png(&bytes) {
+        pngcommand::strip(args, &bytes)?
     } else if is_jpg(&bytes) {
-        jpgcommand::remove(args, &bytes)?;
+        jpgcommand::strip(args, &bytes)?
     } else if is_gif(&bytes) {
-        gifcommand::remove(args, &bytes)?;
+        gifcommand::strip(args, &bytes)?
     } else {
         return Err("No Supported Format".into());
+    };
+
+    let before = segments(&bytes)?;
+    verify::verify_stripped(&before, &segments(&result)?, (&bytes, &result), &stripped)?;
+    let changes = stripped
+        .iter()
+        .map(|item| {
+            let segment = &before[item.index];
+            ChangeReport {
+                action: if item.replacement.is_some() { "replaced" } else { "deleted" },
+                chunk_type: segment.chunk_type.clone(),
+                offset: segment.offset,
+                length: segment.length,
+                category: Some(item.category),
+            }
+        })
+        .collect();
+    write_output(file_path, output, &bytes, &result, changes, &args.write)
+}
+
+/// 删除元数据，并列出删除的块
+pub fn strip(args: StripArgs, format: OutputFormat) -> Result<()> {
+    let inputs = batch::collect(&args.file_path, args.recursive)?;
+    if !batch::is_batch(&args.file_path) {
+        let output = match &args.output {
+            Some(output) => output.clone(),
+            None => batch::output_path(&inputs[0], args.output_dir.as_deref()),
+        };
+        let report = strip_file(&args, &args.file_path, &output)?;
+        // 图像写入标准输出时，改动列表输出到标准错误
+        if batch::is_stdio(&output) && !report.dry_run {
+            let text = match format {
+                OutputFormat::Text => report.text(),
+                _ => report::render(&report, format)?,
+            };
+            eprint!("{}", text);
+            return Ok(());
+        }
+        if format == OutputFormat::Text {
+            report.print();
+            return Ok(());
+        }
+        return report::emit(&report, format);
+    }
+    if args.output.is_some() {
+        return Err("--output only applies to a single file, use --output-dir instead".into());
     }
 
+    let results = batch::run(&inputs, |input| {
+        let output = batch::output_path(input, args.output_dir.as_deref());
+        strip_file(&args, &input.path, &output)
+    });
+    summarize_writes(BatchReport::new(&inputs, results), format, true)
+}
+
+/// 输出批量处理汇总，有失败时返回错误
+fn summarize<T: serde::Serialize>(summary: BatchReport<T>, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Text {
+        summary.print_summary();
+    } else {
+        report::emit(&summary, format)?;
+    }
+    if summary.failed > 0 {
+        return Err(format!("{} of {} files failed", summary.failed, summary.total).into());
+    }
     Ok(())
 }
 
-pub fn print(args: PrintArgs) -> Result<()> {
-    if !args.file_path.exists() {
-        return Err("File does not exist".into());
+pub fn print(args: PrintArgs, format: OutputFormat) -> Result<()> {
+    let bytes = source::open(&args.file_path)?;
+
+    if format == OutputFormat::Text {
+        if is_png(&bytes) {
+            pngcommand::print(args, &bytes)?;
+        } else if is_jpg(&bytes) {
+            jpgcommand::print(args, &bytes)?;
+        } else if is_gif(&bytes) {
+            gifcommand::print(args, &bytes)?;
+        } else {
+            return Err("No Supported Format".into());
+        }
+        return Ok(());
     }
-    let bytes = fs::read(args.file_path.clone())?;
 
-    if is_png(&bytes) {
-        pngcommand::print(args, &bytes)?;
+    let (file_format, chunks) = if is_png(&bytes) {
+        ("png", pngcommand::report(&args, &bytes)?)
     } else if is_jpg(&bytes) {
-        jpgcommand::print(args, &bytes)?;
+        ("jpg", jpgcommand::report(&args, &bytes)?)
     } else if is_gif(&bytes) {
-        gifcommand::print(args, &bytes)?;
+        ("gif", gifcommand::report(&args, &bytes)?)
     } else {
         return Err("No Supported Format".into());
+    };
+    report::emit(&FileReport {
+        file: args.file_path.display().to_string(),
+        format: file_format,
+        size: bytes.len(),
+        chunks,
+    }, format)?;
+
+    Ok(())
+}
+
+fn scan_file(file_path: &Path) -> Result<(&'static str, Vec<SlotReport>)> {
+    let bytes = source::open(file_path)?;
+
+    if is_png(&bytes) {
+        Ok(("png", pngcommand::scan(&bytes)?))
+    } else if is_jpg(&bytes) {
+        Ok(("jpg", jpgcommand::scan(&bytes)?))
+    } else if is_gif(&bytes) {
+        Ok(("gif", gifcommand::scan(&bytes)?))
+    } else {
+        Err("No Supported Format".into())
+    }
+}
+
+/// 文本输出：每个文件一行，之后列出其中的槽位
+/// 载荷状态：明文，或加密及各项可知的参数，例如`encrypted (argon2id, chacha20poly1305)`
+fn payload_state(payload: &PayloadInfo) -> String {
+    if !payload.encrypted {
+        return "plain".to_owned();
+    }
+    let key_id = payload.key_id.as_ref().map(|id| format!("key {}", id));
+    let details: Vec<&str> = [payload.kdf, payload.cipher, key_id.as_deref()].into_iter().flatten().collect();
+    if details.is_empty() {
+        return "encrypted".to_owned();
+    }
+    format!("encrypted ({})", details.join(", "))
+}
+
+fn print_scan(reports: &[ScanReport]) {
+    for report in reports {
+        if let Some(error) = &report.error {
+            eprintln!("{}: Error: {}", report.file, error);
+            continue;
+        }
+        println!("{} ({}, {} slots)", report.file, report.format.unwrap_or_default(), report.slots.len());
+        for slot in &report.slots {
+            println!("  {:<6} @ {:#010x}  {:>8} bytes  {}", slot.chunk_type, slot.offset, slot.payload.size, payload_state(&slot.payload));
+        }
+    }
+}
+
+pub fn scan(args: ScanArgs, format: OutputFormat) -> Result<()> {
+    let mut inputs: Vec<Input> = Vec::new();
+    for file_path in &args.files {
+        inputs.extend(batch::collect(file_path, args.recursive)?);
+    }
+    let reports: Vec<ScanReport> = batch::run(&inputs, |input| scan_file(&input.path))
+        .into_iter()
+        .zip(&inputs)
+        .map(|(result, input)| {
+            let file = input.path.display().to_string();
+            match result {
+                Ok((file_format, slots)) => ScanReport { file, format: Some(file_format), slots, error: None },
+                Err(e) => ScanReport { file, format: None, slots: Vec::new(), error: Some(e) },
+            }
+        })
+        .collect();
+
+    if format == OutputFormat::Text {
+        print_scan(&reports);
+    } else {
+        report::emit(&reports, format)?;
+    }
+    // 与批量处理一样，有文件出错时以非零状态退出
+    let failed = reports.iter().filter(|report| report.error.is_some()).count();
+    if failed > 0 {
+        return Err(format!("{} of {} files failed", failed, reports.len()).into());
+    }
+    Ok(())
+}
+
+/// 保险库或封印所在块的类型，未指定块类型时为命名槽位
+fn named_chunk_type(chunk_type: Option<&str>, name: &str) -> Result<String> {
+    match chunk_type {
+        Some(chunk_type) => Ok(chunk_type.to_owned()),
+        None => slot::resolve("", Some(name)),
+    }
+}
+
+/// 读出并解密保险库，文件中还没有该块时返回空的保险库
+/// 读取保险库，同时返回块中原来的载荷，写回时沿用其中的salt与算法
+fn read_vault(args: &VaultFileArgs, chunk_type: &str, keys: &KeyCache) -> Result<(Vault, Option<String>)> {
+    let bytes = source::open(&args.file_path)?;
+    if !segments(&bytes)?.iter().any(|segment| segment.chunk_type == chunk_type) {
+        return Ok((Vault::default(), None));
+    }
+    let decode_args = DecodeArgs {
+        chunk_type: chunk_type.to_owned(),
+        ..Default::default()
+    };
+    let (_, payload) = decode_payload(&decode_args, &bytes)?;
+    let vault = Vault::parse(&decrypt_payload(keys, &bytes, chunk_type, &payload)?)?;
+    Ok((vault, String::from_utf8(payload).ok()))
+}
+
+/// 加密保险库并按encode的流程写回同一个块。用同一个密码重新加密时沿用原来的salt，
+/// 已经派生出密钥的读取方不必因为salt改变而失效；未指定新的密码或密钥时，
+/// 加密的保险库用原来的密钥重新加密，不会变成明文
+fn write_vault(
+    args: &VaultFileArgs,
+    chunk_type: &str,
+    vault: &Vault,
+    existing: Option<&str>,
+    keys: &KeyCache,
+    output: Option<&Path>,
+    options: &WriteArgs,
+) -> Result<WriteReport> {
+    let encode_args = EncodeArgs {
+        file_path: args.file_path.clone(),
+        chunk_type: chunk_type.to_owned(),
+        message: vault.to_message()?,
+        output: output.map(Path::to_path_buf),
+        force: true,
+        bind_image: existing.is_some_and(key::binds_image),
+        cipher: existing.and_then(key::cipher).unwrap_or_default(),
+        pad: existing.and_then(key::padding),
+        write: options.clone(),
+        ..Default::default()
+    };
+    let password = args.password.as_deref().filter(|password| !password.is_empty());
+    let key = match (password, existing.and_then(key::salt)) {
+        (Some(_), Some(salt)) => Some(keys.key(&salt)?),
+        _ => match encryption_key(password, args.key_file.as_deref(), args.keyring_key.as_deref())? {
+            Some(key) => Some(Arc::new(key)),
+            None => existing_key(existing, keys)?,
+        },
+    };
+    let output = output.unwrap_or(&args.file_path);
+    encode_file(&encode_args, &args.file_path, output, key.as_deref(), keys)
+}
+
+/// 保险库原来的加密密钥：密钥文件与密钥环中按ID查找，明文保险库返回None
+fn existing_key(existing: Option<&str>, keys: &KeyCache) -> Result<Option<Arc<DerivedKey>>> {
+    let Some(info) = existing.map(|existing| key::inspect(existing.as_bytes())).filter(|info| info.encrypted) else {
+        return Ok(None);
+    };
+    match info.key_id {
+        Some(id) => keys.key_file(&id).map(Some).ok_or_else(|| format!("The vault is encrypted with key {}, pass its key file with --key-file or add it to the keyring", id).into()),
+        None => Err("The vault is encrypted, pass its password with -p to write it".into()),
+    }
+}
+
+/// 读写单个块中的键值对
+pub fn vault(args: VaultArgs, format: OutputFormat) -> Result<()> {
+    let file_args = match &args.action {
+        VaultAction::Set(args) => &args.vault,
+        VaultAction::Get(args) => &args.vault,
+        VaultAction::List(args) => &args.vault,
+        VaultAction::Delete(args) => &args.vault,
+    };
+    let is_write = matches!(args.action, VaultAction::Set(_) | VaultAction::Delete(_));
+    if is_write && batch::is_stdio(&file_args.file_path) {
+        return Err("Vault does not support reading from stdin when writing".into());
+    }
+    let chunk_type = named_chunk_type(file_args.chunk_type.as_deref(), &file_args.name)?;
+    let keys = decryption_keys(file_args.password.as_deref(), file_args.key_file.as_deref())?;
+    let (mut vault, existing) = read_vault(file_args, &chunk_type, &keys)?;
+    let file = file_args.file_path.display().to_string();
+
+    match args.action {
+        VaultAction::Set(args) => {
+            vault.set(&args.key, &args.value);
+            let report = write_vault(&args.vault, &chunk_type, &vault, existing.as_deref(), &keys, args.output.as_deref(), &args.write)?;
+            show_write(report, format)
+        }
+        VaultAction::Delete(args) => {
+            vault.delete(&args.key)?;
+            let report = write_vault(&args.vault, &chunk_type, &vault, existing.as_deref(), &keys, args.output.as_deref(), &args.write)?;
+            show_write(report, format)
+        }
+        VaultAction::Get(args) => {
+            let value = vault.get(&args.key).ok_or_else(|| format!("Key {} not found in vault", args.key))?;
+            if format == OutputFormat::Text {
+                println!("{}", value);
+                return Ok(());
+            }
+            report::emit(&VaultEntryReport { file, chunk_type, key: args.key, value: value.to_owned() }, format)
+        }
+        VaultAction::List(_) => {
+            let keys = vault.keys();
+            if format == OutputFormat::Text {
+                for key in &keys {
+                    println!("{}", key);
+                }
+                return Ok(());
+            }
+            report::emit(&VaultListReport { file, chunk_type, keys }, format)
+        }
+    }
+}
+
+/// 关键图像数据：PNG的IHDR、PLTE与IDAT，JPG的帧头与扫描数据，GIF的描述符与图像块
+fn critical_parts(bytes: &[u8]) -> Result<Vec<Part>> {
+    if is_png(bytes) {
+        pngcommand::critical_parts(bytes)
+    } else if is_jpg(bytes) {
+        jpgcommand::critical_parts(bytes)
+    } else if is_gif(bytes) {
+        gifcommand::critical_parts(bytes)
+    } else {
+        Err("No Supported Format".into())
+    }
+}
+
+/// 为关键图像数据生成带MAC的摘要列表，写入载荷块
+pub fn seal(args: SealArgs, format: OutputFormat) -> Result<()> {
+    if batch::is_stdio(&args.file_path) {
+        return Err("Seal does not support reading from stdin".into());
+    }
+    let chunk_type = named_chunk_type(args.chunk_type.as_deref(), &args.name)?;
+    let bytes = source::open(&args.file_path)?;
+    let key = DerivedKey::generate(&args.password)?;
+    let seal = Seal::new(format_name(&bytes)?, critical_parts(&bytes)?, &key)?;
+    drop(bytes);
+
+    let encode_args = EncodeArgs {
+        file_path: args.file_path.clone(),
+        chunk_type: chunk_type.clone(),
+        message: seal.to_message()?,
+        output: args.output.clone(),
+        force: true,
+        write: args.write.clone(),
+        ..Default::default()
+    };
+    let output = args.output.as_deref().unwrap_or(&args.file_path);
+    let report = encode_file(&encode_args, &args.file_path, output, None, &KeyCache::new(""))?;
+    show_write(report, format)
+}
+
+/// 校验封印的MAC，重新计算关键图像数据的摘要并列出发生变化的块
+pub fn check_seal(args: CheckSealArgs, format: OutputFormat) -> Result<()> {
+    let chunk_type = named_chunk_type(args.chunk_type.as_deref(), &args.name)?;
+    let bytes = source::open(&args.file_path)?;
+    let decode_args = DecodeArgs {
+        chunk_type: chunk_type.clone(),
+        ..Default::default()
+    };
+    let (file_format, payload) = decode_payload(&decode_args, &bytes)?;
+    let seal = Seal::parse(std::str::from_utf8(&payload).map_err(|_| "Invalid UTF-8")?)?;
+    seal.verify(&args.password)?;
+    if seal.format() != file_format {
+        return Err(format!("Seal was made for a {} file, not {}", seal.format(), file_format).into());
+    }
+
+    let parts: Vec<SealPartReport> = seal
+        .compare(&critical_parts(&bytes)?)
+        .into_iter()
+        .map(|(index, chunk_type, status)| SealPartReport { index, chunk_type, status })
+        .collect();
+    let report = SealReport {
+        file: args.file_path.display().to_string(),
+        format: file_format,
+        chunk_type,
+        intact: parts.iter().all(|part| part.status == Status::Unchanged),
+        parts,
+    };
+
+    if format == OutputFormat::Text {
+        let changed: Vec<&SealPartReport> = report.parts.iter().filter(|part| part.status != Status::Unchanged).collect();
+        println!("{} ({}, {} parts, {} changed)", report.file, report.format, report.parts.len(), changed.len());
+        for part in changed {
+            println!("  #{:<4} {:<8} {}", part.index, part.chunk_type, part.status.name());
+        }
+    } else {
+        report::emit(&report, format)?;
+    }
+    if !report.intact {
+        return Err("Image was modified after it was sealed".into());
+    }
+    Ok(())
+}
+
+/// 生成随机的对称密钥文件，未指定输出文件时打印到标准输出
+pub fn keygen(args: KeygenArgs, format: OutputFormat) -> Result<()> {
+    let file = KeyFile::generate();
+    let text = file.to_text()?;
+    let Some(output) = &args.output else {
+        println!("{}", text);
+        return Ok(());
+    };
+    write::write_private(output, format!("{}\n", text).as_bytes())?;
+
+    let report = KeygenReport { id: file.id().to_owned(), path: output.display().to_string() };
+    if format == OutputFormat::Text {
+        println!("{}: {}", report.path, report.id);
+        return Ok(());
+    }
+    report::emit(&report, format)
+}
+
+/// 管理密钥环中的密钥
+pub fn key(args: KeyArgs, format: OutputFormat) -> Result<()> {
+    let show = |file: &KeyFile| KeyReport { name: file.name().unwrap_or_default().to_owned(), id: file.id().to_owned() };
+    match args.action {
+        KeyAction::Add(args) => {
+            let file = match &args.from {
+                Some(path) => keyfile::open(path)?,
+                None => KeyFile::generate(),
+            };
+            let report = show(&keyring::add(&args.name, file)?);
+            if format == OutputFormat::Text {
+                println!("{}: {}", report.name, report.id);
+                return Ok(());
+            }
+            report::emit(&report, format)
+        }
+        KeyAction::List => {
+            let reports: Vec<KeyReport> = keyring::list()?.iter().map(show).collect();
+            if format == OutputFormat::Text {
+                for report in &reports {
+                    println!("{}: {}", report.name, report.id);
+                }
+                return Ok(());
+            }
+            report::emit(&reports, format)
+        }
+        KeyAction::Remove(args) => {
+            let report = show(&keyring::remove(&args.name)?);
+            if format == OutputFormat::Text {
+                println!("Removed {}: {}", report.name, report.id);
+                return Ok(());
+            }
+            report::emit(&report, format)
+        }
+        KeyAction::Export(args) => {
+            let text = keyring::get(&args.name)?.to_text()?;
+            match &args.output {
+                Some(output) => write::write_private(output, format!("{}\n", text).as_bytes()),
+                None => {
+                    println!("{}", text);
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// 份额或分片加密时的关联数据：它们不属于某一个图像，只绑定到槽位
+fn detached_context<'a>(kind: &'a str, chunk_type: &'a str) -> Context<'a> {
+    Context { format: kind, chunk_type, image: None }
+}
+
+/// 展开图像列表，每个图像写入一份，不能重复
+fn collect_carriers(files: &[PathBuf]) -> Result<Vec<Input>> {
+    let mut inputs: Vec<Input> = Vec::new();
+    for file_path in files {
+        inputs.extend(batch::collect(file_path, false)?);
+    }
+    if inputs.iter().enumerate().any(|(index, input)| inputs[..index].iter().any(|other| other.path == input.path)) {
+        return Err("Each part needs a different image".into());
+    }
+    Ok(inputs)
+}
+
+/// 写入任何一份之前先检查所有图像，避免只写入了一部分
+fn check_free_slots(inputs: &[Input], chunk_type: &str) -> Result<()> {
+    let decode_args = DecodeArgs { chunk_type: chunk_type.to_owned(), ..Default::default() };
+    for input in inputs {
+        if decode_payload(&decode_args, &source::open(&input.path)?).is_ok() {
+            return Err(format!("{}: Chunk {} already holds a payload, use --force to overwrite it", input.path.display(), chunk_type).into());
+        }
     }
-    
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 按encode的流程把`messages`依次写入各个图像
+fn write_parts(inputs: &[Input], chunk_type: &str, messages: &[String], output_dir: Option<&Path>, options: &WriteArgs) -> BatchReport<WriteReport> {
+    let results = batch::run(inputs, |input| {
+        let index = inputs.iter().position(|other| other.path == input.path).unwrap_or_default();
+        let encode_args = EncodeArgs {
+            file_path: input.path.clone(),
+            chunk_type: chunk_type.to_owned(),
+            message: messages[index].clone(),
+            force: true,
+            write: options.clone(),
+            ..Default::default()
+        };
+        encode_file(&encode_args, &input.path, &batch::output_path(input, output_dir), None, &KeyCache::new(""))
+    });
+    BatchReport::new(inputs, results)
+}
+
+/// 读取各图像槽位中的份额或分片，缺少或损坏的图像只输出错误
+fn read_parts<T, F>(inputs: &[Input], chunk_type: &str, parse: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&str) -> Option<Result<T>> + Sync,
+{
+    let decode_args = DecodeArgs { chunk_type: chunk_type.to_owned(), ..Default::default() };
+    let results = batch::run(inputs, |input| {
+        let bytes = source::open(&input.path)?;
+        let (_, payload) = decode_payload(&decode_args, &bytes)?;
+        parse(std::str::from_utf8(&payload).map_err(|_| "Invalid UTF-8")?).ok_or("Payload is not part of a split or spread payload")?
+    });
+    let mut parts = Vec::new();
+    for (result, input) in results.into_iter().zip(inputs) {
+        match result {
+            Ok(part) => parts.push(part),
+            Err(e) => eprintln!("{}: Error: {}", input.path.display(), e),
+        }
+    }
+    parts
+}
+
+/// 多次拆分的份额或分片混在一起时，使用数量最多的一组
+fn largest_set<T>(parts: &mut Vec<T>, set: impl Fn(&T) -> &str) -> Result<String> {
+    let largest = parts
+        .iter()
+        .map(&set)
+        .max_by_key(|id| parts.iter().filter(|part| set(part) == *id).count())
+        .map(str::to_owned)
+        .ok_or("No parts found")?;
+    parts.retain(|part| set(part) == largest);
+    Ok(largest)
+}
+
+/// 拆分秘密（可先加密），每个图像写入一份
+pub fn split(args: SplitArgs, format: OutputFormat) -> Result<()> {
+    let chunk_type = named_chunk_type(args.chunk_type.as_deref(), &args.name)?;
+    let inputs = collect_carriers(&args.files)?;
+    if inputs.len() != args.shares as usize {
+        return Err(format!("Expected {} images for {} shares, got {}", args.shares, args.shares, inputs.len()).into());
+    }
+    if !args.force {
+        check_free_slots(&inputs, &chunk_type)?;
+    }
+
+    let secret = match encryption_key(args.password.as_deref(), args.key_file.as_deref(), args.key.as_deref())? {
+        Some(key) => key::encrypt_with(&args.message, &key, args.cipher, args.pad, &detached_context("shares", &chunk_type))?,
+        None => args.message.clone(),
+    };
+    let messages: Vec<String> = share::split(secret.as_bytes(), args.threshold, args.shares)?.iter().map(Share::to_message).collect();
+    summarize_writes(write_parts(&inputs, &chunk_type, &messages, args.output_dir.as_deref(), &args.write), format, args.write.dry_run)
+}
+
+/// 读取各图像中的份额，用同一次拆分中足够的份额恢复秘密并解密
+pub fn combine(args: CombineArgs, format: OutputFormat) -> Result<()> {
+    let chunk_type = named_chunk_type(args.chunk_type.as_deref(), &args.name)?;
+    let mut inputs: Vec<Input> = Vec::new();
+    for file_path in &args.files {
+        inputs.extend(batch::collect(file_path, args.recursive)?);
+    }
+    // 缺少份额或份额损坏的图像不影响恢复，只要剩下的份额足够
+    let mut shares = read_parts(&inputs, &chunk_type, Share::parse);
+    let set = largest_set(&mut shares, |share| &share.set)?;
+    shares.sort_by_key(Share::index);
+    shares.dedup_by_key(|share| share.index());
+    let threshold = shares[0].threshold;
+    if shares.len() < threshold as usize {
+        return Err(format!("Need {} shares to recover the secret, found {}", threshold, shares.len()).into());
+    }
+
+    let secret = String::from_utf8(share::combine(&shares)?)?;
+    let keys = decryption_keys(args.password.as_deref(), args.key_file.as_deref())?;
+    let report = CombineReport {
+        set,
+        threshold,
+        shares: shares.iter().map(Share::index).collect(),
+        payload: key::inspect(secret.as_bytes()),
+        message: keys.decrypt(&secret, &detached_context("shares", &chunk_type))?,
+    };
+    if format == OutputFormat::Text {
+        println!("{}", report.message);
+        return Ok(());
+    }
+    report::emit(&report, format)
+}
+
+/// 把载荷（可先加密）分散到FILE_PATH与--spread指定的图像中，附带Reed-Solomon校验分片
+fn encode_spread(args: EncodeArgs, format: OutputFormat) -> Result<()> {
+    let mut files = vec![args.file_path.clone()];
+    files.extend(args.spread.iter().cloned());
+    let inputs = collect_carriers(&files)?;
+    if !args.force {
+        check_free_slots(&inputs, &args.chunk_type)?;
+    }
+
+    let payload = match encryption_key(args.password.as_deref(), args.key_file.as_deref(), args.key.as_deref())? {
+        Some(key) => key::encrypt_with(&args.message, &key, args.cipher, args.pad, &detached_context("spread", &args.chunk_type))?,
+        None => args.message.clone(),
+    };
+    let messages: Vec<String> = spread::split(payload.as_bytes(), inputs.len(), args.parity)?.iter().map(Fragment::to_message).collect();
+    summarize_writes(write_parts(&inputs, &args.chunk_type, &messages, args.output_dir.as_deref(), &args.write), format, args.write.dry_run)
+}
+
+/// 从FILE_PATH下的图像中收集分片，恢复分散写入的载荷
+fn decode_spread(args: &DecodeArgs, keys: &KeyCache) -> Result<DecodeReport> {
+    let inputs = batch::collect(&args.file_path, args.recursive)?;
+    let mut fragments = read_parts(&inputs, &args.chunk_type, Fragment::parse);
+    largest_set(&mut fragments, |fragment| &fragment.set)?;
+    let payload = spread::join(&fragments)?;
+    Ok(DecodeReport {
+        file: args.file_path.display().to_string(),
+        format: "spread",
+        chunk_type: args.chunk_type.clone(),
+        payload: key::inspect(&payload),
+        message: keys.decrypt(std::str::from_utf8(&payload).map_err(|_| "Invalid UTF-8")?, &detached_context("spread", &args.chunk_type))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::fixtures::{self, TempFile};
+
+    fn rekey_args(args: &[&str]) -> RekeyArgs {
+        match Cli::try_parse_from([&["pngkey", "rekey", "missing.png", "ruSt"], args].concat()).unwrap().subcommand {
+            Some(PngKeyArgs::Rekey(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn rekey_without_new_secret_is_rejected() {
+        for args in [&["--old-password", "123456"][..], &["--new-password", ""]] {
+            let error = rekey(rekey_args(args), OutputFormat::Text).unwrap_err();
+            assert!(error.to_string().starts_with("No new secret given"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rekey_decrypt_conflicts_with_new_secret() {
+        assert!(Cli::try_parse_from(["pngkey", "rekey", "a.png", "ruSt", "--decrypt", "--new-password", "1"]).is_err());
+        assert!(rekey_args(&["--old-password", "123456", "--decrypt"]).decrypt);
+    }
+
+    fn encode_file_with(file: &TempFile, args: &[&str]) -> Result<()> {
+        let path = file.path().to_str().unwrap();
+        match Cli::try_parse_from([&["pngkey", "encode", path, "ruSt"], args].concat()).unwrap().subcommand {
+            Some(PngKeyArgs::Encode(args)) => encode(args, OutputFormat::Text),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn old_password_must_decrypt_existing_payload() {
+        let file = TempFile::new("a.png", &fixtures::png());
+        encode_file_with(&file, &["first", "-p", "123456"]).unwrap();
+
+        let error = encode_file_with(&file, &["second", "--old-password", "654321"]).unwrap_err();
+        assert!(error.to_string().contains("--force"), "{}", error);
+        encode_file_with(&file, &["second", "--old-password", "123456"]).unwrap();
+        assert_eq!(raw_payload(&file, "ruSt"), "second");
+    }
+
+    #[test]
+    fn old_password_does_not_overwrite_plain_payload() {
+        let file = TempFile::new("a.png", &fixtures::png());
+        encode_file_with(&file, &["first"]).unwrap();
+
+        let error = encode_file_with(&file, &["second", "--old-password", "123456"]).unwrap_err();
+        assert!(error.to_string().contains("--force"), "{}", error);
+        assert_eq!(raw_payload(&file, "ruSt"), "first");
+        encode_file_with(&file, &["second", "--force"]).unwrap();
+        assert_eq!(raw_payload(&file, "ruSt"), "second");
+    }
+
+    fn run(args: &[&str]) -> Result<()> {
+        match Cli::try_parse_from([&["pngkey"], args].concat()).unwrap().subcommand {
+            Some(PngKeyArgs::Seal(args)) => seal(args, OutputFormat::Json),
+            Some(PngKeyArgs::CheckSeal(args)) => check_seal(args, OutputFormat::Json),
+            _ => unreachable!(),
+        }
+    }
+
+    /// 封印后把`from`改为同样长度的`to`，检查封印能发现改动
+    fn check_seal_tamper(name: &str, bytes: &[u8], from: &[u8], to: &[u8]) {
+        let file = TempFile::new(name, bytes);
+        let path = file.path().to_str().unwrap();
+        run(&["seal", path, "-p", "123456"]).unwrap();
+        run(&["check-seal", path, "-p", "123456"]).unwrap();
+        assert!(run(&["check-seal", path, "-p", "654321"]).is_err());
+
+        let sealed = file.read();
+        let offset = sealed.windows(from.len()).position(|window| window == from).unwrap();
+        let mut tampered = sealed.clone();
+        tampered[offset..offset + to.len()].copy_from_slice(to);
+        fs::write(file.path(), tampered).unwrap();
+        let error = run(&["check-seal", path, "-p", "123456"]).unwrap_err();
+        assert!(error.to_string().contains("modified"), "{}", error);
+    }
+
+    #[test]
+    fn seal_detects_tampered_png() {
+        let idat = fixtures::png_chunk(b"IDAT", &[0x78, 0x9C, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01]);
+        let other = fixtures::png_chunk(b"IDAT", &[0x78, 0x9C, 0x63, 0xF8, 0x0F, 0x00, 0x01, 0x01, 0x01, 0x00]);
+        check_seal_tamper("a.png", &fixtures::png(), &idat, &other);
+    }
+
+    #[test]
+    fn seal_detects_tampered_jpg() {
+        let table = fixtures::jpg_segment(0xDB, &[0; 65]);
+        let other = fixtures::jpg_segment(0xDB, &[1; 65]);
+        check_seal_tamper("a.jpg", &fixtures::jpg(), &table, &other);
+    }
+
+    #[test]
+    fn seal_detects_tampered_gif() {
+        // 只改动图形控制扩展中的帧延迟
+        let control = fixtures::gif_extension(0xF9, &[0, 10, 0, 0]);
+        let other = fixtures::gif_extension(0xF9, &[0, 99, 0, 0]);
+        check_seal_tamper("a.gif", &fixtures::gif(), &control, &other);
+    }
+
+    #[test]
+    fn scan_state_shows_cipher_and_key() {
+        let key_file = KeyFile::generate();
+        let key = key_file.key().unwrap();
+        let context = Context { format: "png", chunk_type: "ruSt", image: None };
+        let encrypted = key::encrypt_with("hi", &key, Cipher::XChaCha20Poly1305, None, &context).unwrap();
+        assert_eq!(
+            payload_state(&key::inspect(encrypted.as_bytes())),
+            format!("encrypted (xchacha20poly1305, key {})", key_file.id())
+        );
+        assert_eq!(payload_state(&key::inspect(b"hi")), "plain");
+    }
+
+    #[test]
+    fn png_scan_skips_critical_chunks() {
+        let png = fixtures::png();
+        let bytes = [&png[..33], &fixtures::png_chunk(b"ruSt", b"a"), &fixtures::png_chunk(b"RUSt", b"b"), &png[33..]].concat();
+        let slots = pngcommand::scan(&bytes).unwrap();
+        assert_eq!(slots.iter().map(|slot| slot.chunk_type.as_str()).collect::<Vec<_>>(), ["ruSt"]);
+    }
+
+    /// 流式写入、覆写与删除的结果与在内存中处理相同
+    fn check_stream_matches_memory(
+        bytes: &[u8],
+        chunk_type: &str,
+        encode: impl Fn(&EncodeArgs, &[u8], &[u8]) -> Result<Vec<u8>>,
+        encode_stream: impl Fn(&EncodeArgs, Cursor<Vec<u8>>, &mut Vec<u8>, &[u8]) -> Result<Rewrite>,
+        remove: impl Fn(&RemoveArgs, &[u8]) -> Result<Vec<u8>>,
+        remove_stream: impl Fn(&RemoveArgs, Cursor<Vec<u8>>, &mut Vec<u8>) -> Result<Rewrite>,
+    ) {
+        let encode_args = EncodeArgs { chunk_type: chunk_type.to_owned(), ..Default::default() };
+        let remove_args = RemoveArgs { chunk_type: chunk_type.to_owned(), ..Default::default() };
+        let stream_encode = |bytes: &[u8], payload: &[u8]| {
+            let mut output = Vec::new();
+            encode_stream(&encode_args, Cursor::new(bytes.to_vec()), &mut output, payload).unwrap();
+            output
+        };
+
+        let added = encode(&encode_args, bytes, b"hello").unwrap();
+        assert_eq!(stream_encode(bytes, b"hello"), added);
+        let replaced = encode(&encode_args, &added, b"a longer payload").unwrap();
+        assert_eq!(stream_encode(&added, b"a longer payload"), replaced);
+
+        let removed = remove(&remove_args, &replaced).unwrap();
+        let mut output = Vec::new();
+        remove_stream(&remove_args, Cursor::new(replaced), &mut output).unwrap();
+        assert_eq!(output, removed);
+        assert_eq!(removed, bytes);
+    }
+
+    #[test]
+    fn png_stream_matches_memory() {
+        for chunk_type in ["ruSt", "name:token"] {
+            check_stream_matches_memory(
+                &fixtures::png(),
+                chunk_type,
+                pngcommand::encode,
+                |args, reader, writer, payload| pngcommand::encode_stream(args, reader, writer, payload),
+                pngcommand::remove,
+                |args, reader, writer| pngcommand::remove_stream(args, reader, writer),
+            );
+        }
+    }
+
+    #[test]
+    fn jpg_stream_matches_memory() {
+        for chunk_type in ["33", "name:token"] {
+            check_stream_matches_memory(
+                &fixtures::jpg(),
+                chunk_type,
+                jpgcommand::encode,
+                |args, reader, writer, payload| jpgcommand::encode_stream(args, reader, writer, payload),
+                jpgcommand::remove,
+                |args, reader, writer| jpgcommand::remove_stream(args, reader, writer),
+            );
+        }
+    }
+
+    /// 按原地追加的计划改写后的文件内容
+    fn apply(bytes: &[u8], append: &write::Append) -> Vec<u8> {
+        [&bytes[..append.offset as usize], &append.tail].concat()
+    }
+
+    #[test]
+    fn png_append_matches_rewrite() {
+        let png = fixtures::png();
+        for chunk_type in ["ruSt", "name:token"] {
+            let args = EncodeArgs { chunk_type: chunk_type.to_owned(), ..Default::default() };
+            let append = pngcommand::append(&args, Cursor::new(&png), b"hello").unwrap().unwrap();
+            let encoded = pngcommand::encode(&args, &png, b"hello").unwrap();
+            assert_eq!(apply(&png, &append), encoded);
+
+            // 已有该块或结尾标记后还有数据时交给完整重写
+            assert!(pngcommand::append(&args, Cursor::new(&encoded), b"hi").unwrap().is_none());
+            let trailing = [&png[..], b"trailing"].concat();
+            assert!(pngcommand::append(&args, Cursor::new(&trailing), b"hi").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn gif_append_matches_rewrite() {
+        let gif = fixtures::gif();
+        for chunk_type in ["abc", "name:token"] {
+            let args = EncodeArgs { chunk_type: chunk_type.to_owned(), ..Default::default() };
+            let append = gifcommand::append(&args, &gif, b"hello").unwrap().unwrap();
+            let encoded = gifcommand::encode(&args, &gif, b"hello").unwrap();
+            assert_eq!(apply(&gif, &append), encoded);
+
+            assert!(gifcommand::append(&args, &encoded, b"hi").unwrap().is_none());
+            let trailing = [&gif[..], b"trailing"].concat();
+            assert!(gifcommand::append(&args, &trailing, b"hi").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn in_place_encode_matches_rewrite() {
+        let png = fixtures::png();
+        let args = EncodeArgs { chunk_type: "ruSt".to_owned(), ..Default::default() };
+        let file = TempFile::new("a.png", &png);
+        encode_file_with(&file, &["hello", "--in-place"]).unwrap();
+        assert_eq!(file.read(), pngcommand::encode(&args, &png, b"hello").unwrap());
+
+        // 已有载荷时回退到完整重写
+        encode_file_with(&file, &["again", "--in-place", "--force"]).unwrap();
+        assert_eq!(file.read(), pngcommand::encode(&args, &png, b"again").unwrap());
+    }
+
+    fn vault_args(file: &TempFile, key_file: Option<&TempFile>) -> VaultFileArgs {
+        VaultFileArgs {
+            file_path: file.path().to_path_buf(),
+            name: "vault".to_owned(),
+            chunk_type: None,
+            password: None,
+            key_file: key_file.map(|file| file.path().to_path_buf()),
+            keyring_key: None,
+        }
+    }
+
+    fn raw_payload(file: &TempFile, chunk_type: &str) -> String {
+        let args = DecodeArgs { chunk_type: chunk_type.to_owned(), ..Default::default() };
+        String::from_utf8(decode_payload(&args, &file.read()).unwrap().1).unwrap()
+    }
+
+    #[test]
+    fn vault_keeps_keyring_encryption() {
+        let key_file = KeyFile::generate();
+        let key_path = TempFile::new("k.json", key_file.to_text().unwrap().as_bytes());
+        let file = TempFile::new("v.png", &fixtures::png());
+        let chunk_type = named_chunk_type(None, "vault").unwrap();
+        // 密钥环中的密钥在解码时自动加入
+        let keys = KeyCache::new("").with_key_files(vec![key_file.key().unwrap()]);
+
+        let mut vault = Vault::default();
+        vault.set("token", "abc");
+        write_vault(&vault_args(&file, Some(&key_path)), &chunk_type, &vault, None, &keys, None, &WriteArgs::default()).unwrap();
+
+        // 不指定密钥修改，仍用原来的密钥加密
+        let args = vault_args(&file, None);
+        let (mut vault, existing) = read_vault(&args, &chunk_type, &keys).unwrap();
+        vault.set("note", "hi");
+        write_vault(&args, &chunk_type, &vault, existing.as_deref(), &keys, None, &WriteArgs::default()).unwrap();
+        let payload = raw_payload(&file, &chunk_type);
+        assert_eq!(key::inspect(payload.as_bytes()).key_id.as_deref(), Some(key_file.id()));
+
+        let (mut vault, existing) = read_vault(&args, &chunk_type, &keys).unwrap();
+        assert_eq!(vault.keys(), ["note", "token"]);
+        vault.delete("token").unwrap();
+        write_vault(&args, &chunk_type, &vault, existing.as_deref(), &keys, None, &WriteArgs::default()).unwrap();
+        let payload = raw_payload(&file, &chunk_type);
+        assert!(key::inspect(payload.as_bytes()).encrypted);
+        assert_eq!(read_vault(&args, &chunk_type, &keys).unwrap().0.keys(), ["note"]);
+
+        // 没有原来的密钥时拒绝写入，而不是写成明文
+        assert!(existing_key(Some(&payload), &KeyCache::new("")).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// PNG块：长度、类型、数据与CRC
+pub fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(&[&chunk_type[..], data].concat());
+    [&(data.len() as u32).to_be_bytes()[..], chunk_type, data, &crc.to_be_bytes()].concat()
+}
+
+/// 1×1灰度PNG，`chunks`插在IHDR之后
+pub fn png_with(chunks: &[Vec<u8>]) -> Vec<u8> {
+    [
+        &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A][..],
+        &png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+        &chunks.concat(),
+        &png_chunk(b"IDAT", &[0x78, 0x9C, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01]),
+        &png_chunk(b"IEND", &[]),
+    ]
+    .concat()
+}
+
+pub fn png() -> Vec<u8> {
+    png_with(&[])
+}
+
+/// JPG段：标记、长度与数据
+pub fn jpg_segment(marker: u8, data: &[u8]) -> Vec<u8> {
+    [&[0xFF, marker][..], &((data.len() + 2) as u16).to_be_bytes(), data].concat()
+}
+
+/// 带JFIF、量化表、帧头与一段扫描数据的JPG，`segments`插在JFIF之后
+pub fn jpg_with(segments: &[Vec<u8>]) -> Vec<u8> {
+    [
+        &[0xFF, 0xD8][..],
+        &jpg_segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"),
+        &segments.concat(),
+        &jpg_segment(0xDB, &[0; 65]),
+        &jpg_segment(0xC0, &[8, 0, 1, 0, 1, 1, 1, 0x11, 0]),
+        &[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00, 0x12, 0xFF, 0x00, 0x34],
+        &[0xFF, 0xD9],
+    ]
+    .concat()
+}
+
+pub fn jpg() -> Vec<u8> {
+    jpg_with(&[])
+}
+
+/// GIF扩展块，数据放在一个子块中
+pub fn gif_extension(label: u8, data: &[u8]) -> Vec<u8> {
+    [&[0x21, label, data.len() as u8][..], data, &[0]].concat()
+}
+
+/// 带全局调色板、图形控制扩展与一帧图像的1×1 GIF，`extensions`插在图形控制扩展之前
+pub fn gif_with(extensions: &[Vec<u8>]) -> Vec<u8> {
+    [
+        &b"GIF89a"[..],
+        &[1, 0, 1, 0, 0x80, 0, 0],
+        &[0, 0, 0, 0xFF, 0xFF, 0xFF],
+        &extensions.concat(),
+        &gif_extension(0xF9, &[0, 10, 0, 0]),
+        &[0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0],
+        &[2, 2, 0x44, 0x01, 0],
+        &[0x3B],
+    ]
+    .concat()
+}
+
+pub fn gif() -> Vec<u8> {
+    gif_with(&[])
+}
+
+
+/// 小端TIFF数据，IFD0中有相机厂商与方向两个标签
+pub fn exif(orientation: u16) -> Vec<u8> {
+    [
+        &b"II\x2a\x00"[..],
+        &8u32.to_le_bytes(),
+        &2u16.to_le_bytes(),
+        &[0x0F, 0x01, 2, 0], &4u32.to_le_bytes(), b"pk\0\0",
+        &[0x12, 0x01, 3, 0], &1u32.to_le_bytes(), &orientation.to_le_bytes(), &[0, 0],
+        &0u32.to_le_bytes(),
+    ]
+    .concat()
+}
+
+/// 测试结束时删除的临时文件
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(name: &str, bytes: &[u8]) -> TempFile {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "pngkey-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        TempFile(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.0.parent().unwrap()
+    }
+
+    pub fn read(&self) -> Vec<u8> {
+        fs::read(&self.0).unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.dir());
+    }
+}
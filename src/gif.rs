@@ -1,7 +1,11 @@
 use std::io::Write;
-use std::{convert::TryFrom, io::Read};
+use std::convert::TryFrom;
+use std::ops::Range;
 
 mod chunk;
+mod describe;
+mod slot;
+mod strip;
 pub mod command;
 
 use crate::{Error, Result};
@@ -17,12 +21,17 @@ impl Gif {
 
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         if let Some(index) = self.chunk_by_type(chunk_type) {
-            return Ok(self.chunks.remove(index));
+            Ok(self.chunks.remove(index))
         } else {
-            return Err(format!("GIF does not contain chunk type {}", chunk_type).into());
+            Err(format!("GIF does not contain chunk type {}", chunk_type).into())
         }
     }
 
+    /// 移除特定位置的块
+    pub fn remove_chunk_at(&mut self, index: usize) -> Chunk {
+        self.chunks.remove(index)
+    }
+
     pub fn modify_chunk(&mut self, index: usize, data: Vec<u8>) {
         if let Chunk::Extension(chunk) = &mut self.chunks[index] {
             let identifier: &[u8; 8] = &chunk.data[1..9].try_into().unwrap();
@@ -34,61 +43,82 @@ impl Gif {
 
     const IDENTIFIER: [u8; 8] = [b' ', b'p', b'n', b'g', b'k', b'e', b'y', b' '];
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<usize> {
-        let chunk_type_code = chunk_type.as_bytes().to_vec();
-        let index = self
+        self
             .chunks.iter()
-            .position(|c| matches!(c, Chunk::Extension(e) if e.extension_type == 0xFF && e.data[1..9].to_vec() == Self::IDENTIFIER && e.data[9..12].to_vec() == chunk_type_code));
-        index
+            .position(|c| matches!(c, Chunk::Extension(e) if slot::slot_type(&e.borrowed()).as_deref() == Some(chunk_type)))
     }
 
     pub fn as_bytes(&self) -> Result<Vec<u8>> {
         let mut bytes: Vec<u8> = Vec::new();
-
         for chunk in &self.chunks {
-            match chunk {
-                Chunk::Header(header) => {
-                    bytes.write_all(header)?;
-                }
-                Chunk::LogicalScreenDescriptor(lsd) => {
-                    bytes.write_all(&lsd.width.to_le_bytes())?;
-                    bytes.write_all(&lsd.height.to_le_bytes())?;
-                    bytes.write_all(&[lsd.packed_fields])?;
-                    bytes.write_all(&[lsd.background_color_index])?;
-                    bytes.write_all(&[lsd.pixel_aspect_ratio])?;
-                }
-                Chunk::GlobalColorTable(gct) => {
-                    bytes.write_all(gct)?;
-                }
-                Chunk::Image(image) => {
-                    bytes.write_all(&[0x2c])?;
-                    bytes.write_all(&image.descriptor.left.to_le_bytes())?;
-                    bytes.write_all(&image.descriptor.top.to_le_bytes())?;
-                    bytes.write_all(&image.descriptor.width.to_le_bytes())?;
-                    bytes.write_all(&image.descriptor.height.to_le_bytes())?;
-                    bytes.write_all(&[image.descriptor.packed_fields])?;
-                    
-                    if let Some(lct) = &image.local_color_table {
-                        bytes.write_all(lct)?;
-                    }
-
-                    bytes.write_all(&image.image_data)?;
-                }
-                Chunk::Extension(extension) => {
-                    bytes.write_all(&[0x21])?;
-                    bytes.write_all(&[extension.extension_type])?;
-                    bytes.write_all(&extension.data)?;
-                }
-                Chunk::Trailer => {
-                    bytes.write_all(&[0x3b])?;
+            Self::write_chunk(&mut bytes, chunk)?;
+        }
+        Ok(bytes)
+    }
+
+    fn write_chunk<W: Write>(bytes: &mut W, chunk: &Chunk) -> Result<()> {
+        match chunk {
+            Chunk::Header(header) => {
+                bytes.write_all(header)?;
+            }
+            Chunk::LogicalScreenDescriptor(lsd) => {
+                bytes.write_all(&lsd.width.to_le_bytes())?;
+                bytes.write_all(&lsd.height.to_le_bytes())?;
+                bytes.write_all(&[lsd.packed_fields])?;
+                bytes.write_all(&[lsd.background_color_index])?;
+                bytes.write_all(&[lsd.pixel_aspect_ratio])?;
+            }
+            Chunk::GlobalColorTable(gct) => {
+                bytes.write_all(gct)?;
+            }
+            Chunk::Image(image) => {
+                bytes.write_all(&[0x2c])?;
+                bytes.write_all(&image.descriptor.left.to_le_bytes())?;
+                bytes.write_all(&image.descriptor.top.to_le_bytes())?;
+                bytes.write_all(&image.descriptor.width.to_le_bytes())?;
+                bytes.write_all(&image.descriptor.height.to_le_bytes())?;
+                bytes.write_all(&[image.descriptor.packed_fields])?;
+                
+                if let Some(lct) = &image.local_color_table {
+                    bytes.write_all(lct)?;
                 }
+
+                bytes.write_all(&image.image_data)?;
+            }
+            Chunk::Extension(extension) => {
+                bytes.write_all(&[0x21])?;
+                bytes.write_all(&[extension.extension_type])?;
+                bytes.write_all(&extension.data)?;
+            }
+            Chunk::Trailer => {
+                bytes.write_all(&[0x3b])?;
             }
         }
-
-        Ok(bytes)
+        Ok(())
     }
 
     /// 添加自定义Application Extension（用于存储UTF-8数据）
     pub fn add_application_extension(&mut self, identifier: &[u8; 8], auth_code: &[u8; 3], data: &[u8]) -> Result<()> {
+        let extension = Self::application_extension(identifier, auth_code, data);
+
+        // 插入到Trailer之前
+        if let Some(trailer_pos) = self.chunks.iter().rposition(|c| matches!(c, Chunk::Trailer)) {
+            self.chunks.insert(trailer_pos, Chunk::Extension(extension));
+        } else {
+            self.chunks.push(Chunk::Extension(extension));
+        }
+        
+        Ok(())
+    }
+
+    /// Application Extension的原始字节，用于原地追加
+    pub fn application_extension_bytes(identifier: &[u8; 8], auth_code: &[u8; 3], data: &[u8]) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        Self::write_chunk(&mut bytes, &Chunk::Extension(Self::application_extension(identifier, auth_code, data)))?;
+        Ok(bytes)
+    }
+
+    fn application_extension(identifier: &[u8; 8], auth_code: &[u8; 3], data: &[u8]) -> ExtensionChunk {
         // 构建Application Extension数据
         let mut ext_data = Vec::new();
         
@@ -110,61 +140,101 @@ impl Gif {
         ext_data.push(0x00);
         
         // 创建扩展块（类型0xFF = Application Extension）
-        let extension = ExtensionChunk {
+        ExtensionChunk {
             extension_type: 0xFF,
             data: ext_data,
-        };
-        
-        // 插入到Trailer之前
-        if let Some(trailer_pos) = self.chunks.iter().rposition(|c| matches!(c, Chunk::Trailer)) {
-            self.chunks.insert(trailer_pos, Chunk::Extension(extension));
-        } else {
-            self.chunks.push(Chunk::Extension(extension));
         }
-        
-        Ok(())
     }
-    
+}
+
+pub fn is_gif(bytes: &[u8]) -> bool {
+    bytes.len() >= 6 && &bytes[0..6] == b"GIF89a"
+}
+
+impl TryFrom<&[u8]> for Gif {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Gif> {
+        Ok(GifRef::try_from(bytes)?.to_gif())
+    }
+}
+
+/// 借用源数据的GIF结构，decode、print、scan等只读操作不复制块数据
+#[derive(Debug)]
+pub struct GifRef<'a> {
+    bytes: &'a [u8],
+    chunks: Vec<ChunkRef<'a>>,
+    /// 每个块在源文件中的范围
+    spans: Vec<Range<usize>>,
+}
+
+impl<'a> GifRef<'a> {
+    pub fn chunks(&self) -> &[ChunkRef<'a>] {
+        &self.chunks
+    }
+
+    /// 块在源文件中的偏移
+    pub fn offset(&self, index: usize) -> usize {
+        self.spans[index].start
+    }
+
+    /// 块在源文件中的原始字节
+    pub fn chunk_bytes(&self, index: usize) -> &'a [u8] {
+        &self.bytes[self.spans[index].clone()]
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<usize> {
+        self
+            .chunks.iter()
+            .position(|c| matches!(c, ChunkRef::Extension(e) if slot::slot_type(e).as_deref() == Some(chunk_type)))
+    }
+
     /// 提取Application Extension中的数据
-    pub fn extract_application_extensions(&self, chunk_type: &str) -> Option<Vec<u8>> {       
-        for chunk in &self.chunks {
-            if let Chunk::Extension(ext) = chunk {
-                if ext.extension_type == 0xFF && ext.data.len() >= 11 {
-                    let auth_code = String::from_utf8_lossy(&ext.data[9..12]).to_string();
-                    if ext.data[1..9] != Self::IDENTIFIER || auth_code != chunk_type {
-                        continue; // 跳过非目标扩展块
-                    }
-                    
-                    // 解析子块数据
-                    let mut data = Vec::new();
-                    let mut pos = 12;
-                    while pos < ext.data.len() {
-                        let block_size = ext.data[pos] as usize;
-                        if block_size == 0 {
-                            break; // 结束标记
-                        }
-                        pos += 1;
-                        if pos + block_size > ext.data.len() {
-                            break; // 数据损坏
-                        }
-                        data.extend_from_slice(&ext.data[pos..pos + block_size]);
-                        pos += block_size;
-                    }
-                    
-                    return Some(data);
-                }
-            }
+    pub fn extract_application_extensions(&self, chunk_type: &str) -> Option<Vec<u8>> {
+        let index = self.chunk_by_type(chunk_type)?;
+        let ChunkRef::Extension(ext) = &self.chunks[index] else {
+            return None;
+        };
+        // 解析子块数据，命名槽位去掉名称头
+        slot::payload(ext)
+    }
+
+    /// 复制出可修改的GIF
+    pub fn to_gif(&self) -> Gif {
+        Gif {
+            chunks: self.chunks.iter().map(ChunkRef::to_chunk).collect(),
         }
-        
-        None
     }
+}
 
-    /// 内部辅助方法
-    
-    fn read_logical_screen_descriptor<R: Read>(reader: &mut R) -> Result<LogicalScreenDescriptor> {
-        let mut buf = [0u8; 7];
-        reader.read_exact(&mut buf)?;
-        
+/// 在源数据上顺序读取，返回的切片借用源数据
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self.position + length;
+        if end > self.bytes.len() {
+            return Err("Unexpected end of GIF data".into());
+        }
+        let data = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(data)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// 从`start`到当前位置的数据
+    fn since(&self, start: usize) -> &'a [u8] {
+        &self.bytes[start..self.position]
+    }
+
+    fn logical_screen_descriptor(&mut self) -> Result<LogicalScreenDescriptor> {
+        let buf = self.take(7)?;
         Ok(LogicalScreenDescriptor {
             width: u16::from_le_bytes([buf[0], buf[1]]),
             height: u16::from_le_bytes([buf[2], buf[3]]),
@@ -173,11 +243,9 @@ impl Gif {
             pixel_aspect_ratio: buf[6],
         })
     }
-    
-    fn read_image_chunk<R: Read>(reader: &mut R) -> Result<ImageChunk> {
-        let mut buf = [0u8; 9];
-        reader.read_exact(&mut buf)?;
-        
+
+    fn image(&mut self) -> Result<ImageRef<'a>> {
+        let buf = self.take(9)?;
         let descriptor = ImageDescriptor {
             left: u16::from_le_bytes([buf[0], buf[1]]),
             top: u16::from_le_bytes([buf[2], buf[3]]),
@@ -185,123 +253,95 @@ impl Gif {
             height: u16::from_le_bytes([buf[6], buf[7]]),
             packed_fields: buf[8],
         };
-        
+
         // 检查局部调色板
         let local_color_table = if (descriptor.packed_fields & 0x80) != 0 {
-            let size = 2u16.pow(((descriptor.packed_fields & 0x07) + 1) as u32) as usize;
-            let mut lct = vec![0u8; size * 3];
-            reader.read_exact(&mut lct)?;
-            Some(lct)
+            Some(self.take(color_table_size(descriptor.packed_fields) * 3)?)
         } else {
             None
         };
 
-        // 读取图像数据（LZW压缩数据的子块链）
-        let mut lzw_min = [0u8; 1];
-        reader.read_exact(&mut lzw_min)?;
-        
-        let mut image_data = vec![lzw_min[0]];
-        let sub = Self::read_sub_blocks(reader)?;
-        image_data.extend_from_slice(&sub);
-        
-        Ok(ImageChunk {
+        // 图像数据：LZW最小码长加上压缩数据的子块链
+        let start = self.position;
+        self.byte()?;
+        self.sub_blocks()?;
+        Ok(ImageRef {
             descriptor,
             local_color_table,
-            image_data,
+            image_data: self.since(start),
         })
     }
-    
-    fn read_extension_chunk<R: Read>(reader: &mut R) -> Result<ExtensionChunk> {
-        let mut ext_type = [0u8; 1];
-        reader.read_exact(&mut ext_type)?;
-        
-        let mut data = Vec::new();
 
-        let mut sub = Self::read_sub_blocks(reader)?;
-        data.append(&mut sub);
-        
-        Ok(ExtensionChunk {
-            extension_type: ext_type[0],
-            data,
-        })
+    fn extension(&mut self) -> Result<ExtensionRef<'a>> {
+        let extension_type = self.byte()?;
+        let data = self.sub_blocks()?;
+        Ok(ExtensionRef { extension_type, data })
     }
-    
-    fn read_sub_blocks<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
-        let mut data = Vec::new();
+
+    /// 子块链，包含长度字节与结束标记
+    fn sub_blocks(&mut self) -> Result<&'a [u8]> {
+        let start = self.position;
         loop {
-            let mut size_byte = [0u8; 1];
-            reader.read_exact(&mut size_byte)?;
-            data.push(size_byte[0]);
-            
-            let size = size_byte[0] as usize;
+            let size = self.byte()? as usize;
             if size == 0 {
                 break; // 子块链结束
             }
-            
-            let mut block_data = vec![0u8; size];
-            reader.read_exact(&mut block_data)?;
-            data.extend_from_slice(&block_data);
+            self.take(size)?;
         }
-        Ok(data)
+        Ok(self.since(start))
     }
 }
 
-pub fn is_gif(bytes: &[u8]) -> bool {
-    &bytes[0..6] == b"GIF89a"
-}
-
-impl TryFrom<&[u8]> for Gif {
+impl<'a> TryFrom<&'a [u8]> for GifRef<'a> {
     type Error = Error;
 
-    fn try_from(bytes: &[u8]) -> Result<Gif> {
-        let mut cursor = std::io::Cursor::new(bytes);
+    fn try_from(bytes: &'a [u8]) -> Result<GifRef<'a>> {
+        let mut cursor = Cursor { bytes, position: 0 };
         let mut chunks = Vec::new();
-        
-        let mut header = [0u8; 6];
-        cursor.read_exact(&mut header)?;
-        chunks.push(Chunk::Header(header));
+        let mut spans = Vec::new();
+
+        let header: &[u8; 6] = cursor.take(6)?.try_into().unwrap();
+        chunks.push(ChunkRef::Header(header));
+        spans.push(0..cursor.position);
         // if !(&header[..3] == b"GIF") {
         //     return Err("Invalid GIF header".into());
         // }
 
-        let lsd = Self::read_logical_screen_descriptor(&mut cursor)?;
+        let start = cursor.position;
+        let lsd = cursor.logical_screen_descriptor()?;
         let has_gct = (lsd.packed_fields & 0x80) != 0;
-        let gct_size_factor = lsd.packed_fields & 0x07 ;
-        chunks.push(Chunk::LogicalScreenDescriptor(lsd));
+        let gct_size = color_table_size(lsd.packed_fields);
+        chunks.push(ChunkRef::LogicalScreenDescriptor(lsd));
+        spans.push(start..cursor.position);
 
         if has_gct {
-            let size = 2u16.pow((gct_size_factor+1) as u32) as usize;
-            let mut gct = vec![0u8; size * 3]; // RGB / 1 byte
-            cursor.read_exact(&mut gct)?;
-            chunks.push(Chunk::GlobalColorTable(gct));
+            let start = cursor.position;
+            let gct = cursor.take(gct_size * 3)?; // RGB / 1 byte
+            chunks.push(ChunkRef::GlobalColorTable(gct));
+            spans.push(start..cursor.position);
         }
 
         // 读取所有数据直到Trailer
         loop {
-            let mut block_type = [0u8; 1];
-            cursor.read_exact(&mut block_type)?;
-
-            match block_type[0] {
-                0x2c => {
-                    // ','分割图像
-                    let image = Self::read_image_chunk(&mut cursor)?;
-                    chunks.push(Chunk::Image(image));
-                }
-                0x21 => {
-                    // '!'拓展块
-                    let extension = Self::read_extension_chunk(&mut cursor)?;
-                    chunks.push(Chunk::Extension(extension));
-                }
-                0x3b => {
-                    // 结尾
-                    chunks.push(Chunk::Trailer);
-                    break;
-                }
+            let start = cursor.position;
+            let chunk = match cursor.byte()? {
+                // ','分割图像
+                0x2c => ChunkRef::Image(cursor.image()?),
+                // '!'拓展块
+                0x21 => ChunkRef::Extension(cursor.extension()?),
+                // 结尾
+                0x3b => ChunkRef::Trailer,
                 _ => {
                     return Err("Invalid GIF block type".into());
                 }
+            };
+            let end = matches!(chunk, ChunkRef::Trailer);
+            chunks.push(chunk);
+            spans.push(start..cursor.position);
+            if end {
+                break;
             }
         }
-        Ok(Gif { chunks })
+        Ok(GifRef { bytes, chunks, spans })
     }
 }
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use super::describe::describe_extension;
+
 #[derive(Debug, Clone)]
 pub enum Chunk {
     Header([u8; 6]),
@@ -10,25 +12,99 @@ pub enum Chunk {
     Trailer,
 }
 
+impl Chunk {
+    /// 借用为只读视图
+    pub fn borrowed(&self) -> ChunkRef<'_> {
+        match self {
+            Chunk::Header(header) => ChunkRef::Header(header),
+            Chunk::LogicalScreenDescriptor(lsd) => ChunkRef::LogicalScreenDescriptor(lsd.clone()),
+            Chunk::GlobalColorTable(gct) => ChunkRef::GlobalColorTable(gct),
+            Chunk::Image(image) => ChunkRef::Image(ImageRef {
+                descriptor: image.descriptor.clone(),
+                local_color_table: image.local_color_table.as_deref(),
+                image_data: &image.image_data,
+            }),
+            Chunk::Extension(extension) => ChunkRef::Extension(extension.borrowed()),
+            Chunk::Trailer => ChunkRef::Trailer,
+        }
+    }
+}
+
 impl fmt::Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.borrowed(), f)
+    }
+}
+
+/// 借用源数据的GIF块，只读操作不复制块数据
+#[derive(Debug, Clone)]
+pub enum ChunkRef<'a> {
+    Header(&'a [u8; 6]),
+    LogicalScreenDescriptor(LogicalScreenDescriptor),
+    GlobalColorTable(&'a [u8]),
+    Image(ImageRef<'a>),
+    Extension(ExtensionRef<'a>),
+    Trailer,
+}
+
+impl ChunkRef<'_> {
+    /// 复制出可修改的块
+    pub fn to_chunk(&self) -> Chunk {
         match self {
-            Chunk::Header(data) => {
-                write!(f, "Header: [{}]",
-                       data.iter().map(|&b| format!("{:02x}", b)).collect::<Vec<_>>().join(", "))
-            }
-            Chunk::LogicalScreenDescriptor(data) => write!(f, "{}", data),
-            Chunk::GlobalColorTable(data) => {
-                write!(f, "GlobalColorTable: [{}]",
-                       data.iter().map(|&b| format!("{:02x}", b)).collect::<Vec<_>>().join(", "))
-            }
-            Chunk::Image(data) => write!(f, "{}", data),
-            Chunk::Extension(data) => write!(f, "{}", data),
-            Chunk::Trailer => write!(f, "Trailer"),
+            ChunkRef::Header(header) => Chunk::Header(**header),
+            ChunkRef::LogicalScreenDescriptor(lsd) => Chunk::LogicalScreenDescriptor(lsd.clone()),
+            ChunkRef::GlobalColorTable(gct) => Chunk::GlobalColorTable(gct.to_vec()),
+            ChunkRef::Image(image) => Chunk::Image(ImageChunk {
+                descriptor: image.descriptor.clone(),
+                local_color_table: image.local_color_table.map(<[u8]>::to_vec),
+                image_data: image.image_data.to_vec(),
+            }),
+            ChunkRef::Extension(extension) => Chunk::Extension(ExtensionChunk {
+                extension_type: extension.extension_type,
+                data: extension.data.to_vec(),
+            }),
+            ChunkRef::Trailer => Chunk::Trailer,
         }
     }
 }
 
+impl fmt::Display for ChunkRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkRef::Header(data) => write!(f, "Header: {}", String::from_utf8_lossy(*data)),
+            ChunkRef::LogicalScreenDescriptor(data) => write!(f, "{}", data),
+            ChunkRef::GlobalColorTable(data) => write!(f, "GlobalColorTable: {} colors", data.len() / 3),
+            ChunkRef::Image(data) => write!(f, "{}", data),
+            ChunkRef::Extension(data) => write!(f, "{}", data),
+            ChunkRef::Trailer => write!(f, "Trailer"),
+        }
+    }
+}
+
+/// 拼接子块链中的数据，忽略长度字节，遇到结束标记或数据损坏时停止
+pub fn sub_block_data(blocks: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut pos = 0;
+    while pos < blocks.len() {
+        let block_size = blocks[pos] as usize;
+        if block_size == 0 {
+            break; // 结束标记
+        }
+        pos += 1;
+        if pos + block_size > blocks.len() {
+            break; // 数据损坏
+        }
+        data.extend_from_slice(&blocks[pos..pos + block_size]);
+        pos += block_size;
+    }
+    data
+}
+
+/// 调色板颜色数
+pub fn color_table_size(packed_fields: u8) -> usize {
+    2usize.pow(((packed_fields & 0x07) + 1) as u32)
+}
+
 #[derive(Debug, Clone)]
 pub struct LogicalScreenDescriptor {
     pub width: u16,
@@ -43,7 +119,13 @@ impl fmt::Display for LogicalScreenDescriptor {
         writeln!(f, "LogicalScreenDescriptor {{")?;
         writeln!(f, "  width: {},", self.width)?;
         writeln!(f, "  height: {},", self.height)?;
-        writeln!(f, "  packed_fields: {:#04x},", self.packed_fields)?;
+        if self.packed_fields & 0x80 != 0 {
+            writeln!(f, "  global_color_table: {} colors,", color_table_size(self.packed_fields))?;
+        } else {
+            writeln!(f, "  global_color_table: none,")?;
+        }
+        writeln!(f, "  color_resolution: {} bits,", ((self.packed_fields >> 4) & 0x07) + 1)?;
+        writeln!(f, "  sorted: {},", self.packed_fields & 0x08 != 0)?;
         writeln!(f, "  background_color_index: {},", self.background_color_index)?;
         writeln!(f, "  pixel_aspect_ratio: {},", self.pixel_aspect_ratio)?;
         writeln!(f, "}}")?;
@@ -58,14 +140,23 @@ pub struct ImageChunk {
     pub image_data: Vec<u8>, // 包含LZW压缩数据的子块链
 }
 
-impl fmt::Display for ImageChunk {
+/// 借用源数据的图像块
+#[derive(Debug, Clone)]
+pub struct ImageRef<'a> {
+    pub descriptor: ImageDescriptor,
+    pub local_color_table: Option<&'a [u8]>,
+    pub image_data: &'a [u8],
+}
+
+impl fmt::Display for ImageRef<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "ImageChunk {{")?;
         writeln!(f, "  Descriptor: {}", self.descriptor)?;
-        if let Some(_) = self.local_color_table {
-            writeln!(f, "  <Local Color Table Data>")?;
+        if let Some(lct) = self.local_color_table {
+            writeln!(f, "  Local Color Table: {} colors", lct.len() / 3)?;
         }
-        writeln!(f, "  Image: <Image Data>")?;
+        writeln!(f, "  LZW minimum code size: {}", self.image_data[0])?;
+        writeln!(f, "  Image: <Image Data, {} bytes>", sub_block_data(&self.image_data[1..]).len())?;
         writeln!(f, "}}")?;
         Ok(())
     }
@@ -87,6 +178,7 @@ impl fmt::Display for ImageDescriptor {
         writeln!(f, "    top: {},", self.top)?;
         writeln!(f, "    width: {},", self.width)?;
         writeln!(f, "    height: {},", self.height)?;
+        writeln!(f, "    interlaced: {},", self.packed_fields & 0x40 != 0)?;
         writeln!(f, "    packed_fields: {:#04x},", self.packed_fields)?;
         write!(f, "  }}")?;
         Ok(())
@@ -99,18 +191,61 @@ pub struct ExtensionChunk {
     pub data: Vec<u8>,
 }
 
-impl fmt::Display for ExtensionChunk {
+impl ExtensionChunk {
+    /// 借用为只读视图
+    pub fn borrowed(&self) -> ExtensionRef<'_> {
+        ExtensionRef {
+            extension_type: self.extension_type,
+            data: &self.data,
+        }
+    }
+}
+
+/// 借用源数据的扩展块
+#[derive(Debug, Clone, Copy)]
+pub struct ExtensionRef<'a> {
+    pub extension_type: u8,
+    pub data: &'a [u8],
+}
+
+impl<'a> ExtensionRef<'a> {
+    pub fn name(&self) -> &'static str {
+        match self.extension_type {
+            0x01 => "Plain Text",
+            0xf9 => "Graphic Control",
+            0xfe => "Comment",
+            0xff => "Application",
+            _ => "Unknown",
+        }
+    }
+
+    /// Application Extension的标识符
+    pub fn application_identifier(&self) -> Option<&'a [u8]> {
+        if self.extension_type == 0xff && self.data.len() >= 12 {
+            Some(&self.data[1..9])
+        } else {
+            None
+        }
+    }
+
+    /// Application Extension的认证码
+    pub fn application_auth_code(&self) -> Option<&'a [u8]> {
+        if self.extension_type == 0xff && self.data.len() >= 12 {
+            Some(&self.data[9..12])
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ExtensionRef<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "ExtensionChunk {{")?;
-        writeln!(f, "  Extension type: {:#04x},", self.extension_type)?;
-        if self.extension_type == 0xff {
-            writeln!(f, "  Application Identifier: {}", self.data[1..9].iter().map(|&b| b as char).collect::<String>())?;
-            writeln!(f, "  Application Authentication Code: {}", self.data[9..12].iter().map(|&b| b as char).collect::<String>())?;
-            writeln!(f, "  Data: [{}]", String::from_utf8_lossy(&self.data[12..]))?; // 这里是data的原始数据，明文可能被截断
-        } else {
-            writeln!(f, "  ...Datas...")?;
+        writeln!(f, "  Extension type: {:#04x} ({}),", self.extension_type, self.name())?;
+        for (name, value) in describe_extension(self) {
+            writeln!(f, "  {}: {}", name, value)?;
         }
         writeln!(f, "}}")?;
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,105 +1,272 @@
-use std::fs;
-
 use crate::args::*;
-use crate::gif::chunk::Chunk;
-use super::Gif;
+use crate::gif::chunk::{ChunkRef, ExtensionRef};
+use super::describe::describe;
+use super::slot;
+use super::strip::category;
+use super::{Gif, GifRef};
 use crate::Result;
+use crate::hexdump;
 use crate::key;
+use crate::report::{ChunkReport, SlotReport};
+use crate::seal::Part;
+use crate::verify::Segment;
+use crate::strip::{self, Stripped};
+use crate::write::Append;
 
 fn is_valid_chunk_type(chunk_type: &str) -> bool {
     let bytes = chunk_type.as_bytes();
-    bytes.len() == 3
+    crate::slot::name(chunk_type).is_some() || bytes.len() == 3
 }
 
-static IDENTIFIER: [u8; 8] = [b' ', b'p', b'n', b'g', b'k', b'e', b'y', b' '];
-
-pub fn encode(args: EncodeArgs, bytes: &Vec<u8>) -> Result<()> {
-    // chunk type可用
-    if !is_valid_chunk_type(&args.chunk_type) {
-        return Err(format!("Invalid ChunkType, should be 3 bytes long.").into());
+/// 检查命令行中的块类型，命名槽位使用的认证码保留给--name
+fn check_chunk_type(chunk_type: &str) -> Result<()> {
+    if !is_valid_chunk_type(chunk_type) {
+        return Err("Invalid ChunkType, should be 3 bytes long.".into());
     }
-
-    let password = args.password.unwrap_or_else(|| "".to_string());
-    let message = args.message;
-    let encrypted_message: String;
-    if !password.is_empty() {
-        encrypted_message = key::encrypt(&message, &password)?;
-    } else {
-        encrypted_message = message;
+    if chunk_type.as_bytes() == slot::NAMED_AUTH_CODE {
+        return Err(format!("ChunkType {} is reserved for --name.", chunk_type).into());
     }
+    Ok(())
+}
 
-    let mut gif = Gif::try_from(bytes.as_slice())?;
+pub fn encode(args: &EncodeArgs, bytes: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+    // chunk type可用
+    check_chunk_type(&args.chunk_type)?;
+
+    let mut gif = Gif::try_from(bytes)?;
 
-    let chunk_type: [u8; 3] = args.chunk_type.as_bytes().try_into().unwrap();
+    let (auth_code, data) = slot::chunk_data(&args.chunk_type, payload)?;
     if let Some(index) = gif.chunk_by_type(&args.chunk_type) {
-        gif.modify_chunk(index, encrypted_message.as_bytes().to_vec());
+        gif.modify_chunk(index, data);
     } else {
-        gif.add_application_extension(&IDENTIFIER, &chunk_type, encrypted_message.as_bytes())?;
+        gif.add_application_extension(&Gif::IDENTIFIER, &auth_code, &data)?;
     }
 
-    if let Some(output) = args.output {
-        fs::write(output, gif.as_bytes()?)?;
-    } else {
-        fs::write(args.file_path, gif.as_bytes()?)?;
+    gif.as_bytes()
+}
+
+/// 原地追加的计划：没有该类型的块且文件以Trailer结尾时，只需写入新块与Trailer
+pub fn append(args: &EncodeArgs, bytes: &[u8], payload: &[u8]) -> Result<Option<Append>> {
+    check_chunk_type(&args.chunk_type)?;
+
+    let gif = GifRef::try_from(bytes)?;
+    let last = gif.chunks().len() - 1;
+    let trailer = gif.offset(last);
+    if gif.chunk_by_type(&args.chunk_type).is_some()
+        || !matches!(gif.chunks()[last], ChunkRef::Trailer)
+        || trailer + 1 != bytes.len()
+    {
+        return Ok(None);
     }
 
-    Ok(())
+    let (auth_code, data) = slot::chunk_data(&args.chunk_type, payload)?;
+    let extension = Gif::application_extension_bytes(&Gif::IDENTIFIER, &auth_code, &data)?;
+    Ok(Some(Append {
+        offset: trailer as u64,
+        length: extension.len(),
+        tail: [extension.as_slice(), gif.chunk_bytes(last)].concat(),
+    }))
 }
 
-pub fn decode(args: DecodeArgs, bytes: &Vec<u8>) -> Result<String> {
+pub fn decode(args: &DecodeArgs, bytes: &[u8]) -> Result<Vec<u8>> {
     // chunk type可用
-    if !is_valid_chunk_type(&args.chunk_type) {
-        return Err(format!("Invalid ChunkType, should be 3 bytes long.").into());
-    }
+    check_chunk_type(&args.chunk_type)?;
 
-    let gif = Gif::try_from(bytes.as_slice())?;
-    let message_bytes = gif.extract_application_extensions(&args.chunk_type);
-    if message_bytes.is_none() {
-        return Err("Chunk not found".into());
-    }
-    let message = String::from_utf8(message_bytes.unwrap())?;
-    let password = args.password.unwrap_or_else(|| "".to_string());
-    let decrypted_message = key::decrypt(&message, &password)?;
-    println!("{}", decrypted_message);
-    Ok(decrypted_message)
+    let gif = GifRef::try_from(bytes)?;
+    gif.extract_application_extensions(&args.chunk_type)
+        .ok_or_else(|| "Chunk not found".into())
 }
 
-pub fn remove(args: RemoveArgs, bytes: &Vec<u8>) -> Result<()> {
-    let mut gif = Gif::try_from(bytes.as_slice())?;
+pub fn remove(args: &RemoveArgs, bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut gif = Gif::try_from(bytes)?;
     gif.remove_chunk(&args.chunk_type)?;
-    fs::write(args.file_path, gif.as_bytes()?)?;
-    Ok(())
+    gif.as_bytes()
 }
 
-pub fn print(args: PrintArgs, bytes: &Vec<u8>) -> Result<()> {
+pub fn print(args: PrintArgs, bytes: &[u8]) -> Result<()> {
     let mut chunk_type: String = "".to_string();
     if let Some(_chunk_type) = args.chunk_type {
         chunk_type = _chunk_type;
     }
 
-    let gif = Gif::try_from(bytes.as_slice())?;
+    let gif = GifRef::try_from(bytes)?;
+    let show = |index: usize| {
+        let chunk = &gif.chunks()[index];
+        if args.hex {
+            let offset = gif.offset(index);
+            let data = gif.chunk_bytes(index);
+            let name = pngkey_slot(chunk).unwrap_or_else(|| chunk_name(chunk));
+            hexdump::print_chunk(&name, offset, data.len(), data, offset, args.limit);
+        } else {
+            println!("{}", chunk);
+        }
+    };
 
     if chunk_type.is_empty() {
-        for chunk in &gif.chunks {
-            if args.all {
-                println!("{}", chunk);
-            } else {
-                match chunk {
-                    Chunk::Extension(extchunk) => {
-                        if extchunk.extension_type == 0xff && extchunk.data[1..9] == IDENTIFIER {
-                            println!("{}", chunk);
-                        }
-                    }
-                    _ => {}
-                }
+        for (index, chunk) in gif.chunks().iter().enumerate() {
+            if args.all || pngkey_slot(chunk).is_some() {
+                show(index);
             }
         }
+    } else if let Some(index) = gif.chunk_by_type(&chunk_type) {
+        show(index);
     } else {
-        if let Some(index) = gif.chunk_by_type(&chunk_type) {
-            println!("{}", gif.chunks[index]);
-        } else {
-            return Err("Chunk not found".into());
-        }
+        return Err("Chunk not found".into());
     }
     Ok(())
 }
+
+/// pngkey使用的Application Extension在命令行中的类型
+fn pngkey_slot(chunk: &ChunkRef) -> Option<String> {
+    match chunk {
+        ChunkRef::Extension(extchunk) => slot::slot_type(extchunk),
+        _ => None,
+    }
+}
+
+/// 块的名称
+fn chunk_name(chunk: &ChunkRef) -> String {
+    match chunk {
+        ChunkRef::Header(_) => "Header".to_owned(),
+        ChunkRef::LogicalScreenDescriptor(_) => "LogicalScreenDescriptor".to_owned(),
+        ChunkRef::GlobalColorTable(_) => "GlobalColorTable".to_owned(),
+        ChunkRef::Image(_) => "Image".to_owned(),
+        ChunkRef::Extension(extchunk) => format!("{:#04x}", extchunk.extension_type),
+        ChunkRef::Trailer => "Trailer".to_owned(),
+    }
+}
+
+/// 与文本输出相同：按槽位类型匹配，指定块类型时只列出第一个匹配的块
+pub fn report(args: &PrintArgs, bytes: &[u8]) -> Result<Vec<ChunkReport>> {
+    let gif = GifRef::try_from(bytes)?;
+
+    let mut reports = Vec::new();
+    for (index, chunk) in gif.chunks().iter().enumerate() {
+        let length = gif.chunk_bytes(index).len();
+        let auth_code = pngkey_slot(chunk);
+        let selected = match &args.chunk_type {
+            Some(chunk_type) => auth_code.as_ref() == Some(chunk_type),
+            None => args.all || auth_code.is_some(),
+        };
+        if selected {
+            let (chunk_type, name, data_length) = match chunk {
+                ChunkRef::Image(image) => (chunk_name(chunk), None, image.image_data.len()),
+                ChunkRef::Extension(extchunk) => (
+                    auth_code.clone().unwrap_or_else(|| chunk_name(chunk)),
+                    Some(extchunk.name().to_owned()),
+                    extchunk.data.len(),
+                ),
+                ChunkRef::Trailer => (chunk_name(chunk), None, 0),
+                _ => (chunk_name(chunk), None, length),
+            };
+            let payload = auth_code.and_then(|_| gif.extract_application_extensions(&chunk_type))
+                .map(|data| key::inspect(&data));
+            reports.push(ChunkReport {
+                index,
+                offset: gif.offset(index),
+                length,
+                data_length,
+                chunk_type,
+                name,
+                fields: describe(chunk),
+                payload,
+            });
+            if args.chunk_type.is_some() {
+                break;
+            }
+        }
+    }
+    if args.chunk_type.is_some() && reports.is_empty() {
+        return Err("Chunk not found".into());
+    }
+    Ok(reports)
+}
+
+pub fn scan(bytes: &[u8]) -> Result<Vec<SlotReport>> {
+    let gif = GifRef::try_from(bytes)?;
+    let mut slots = Vec::new();
+    for (index, chunk) in gif.chunks().iter().enumerate() {
+        if let (Some(chunk_type), ChunkRef::Extension(extchunk)) = (pngkey_slot(chunk), chunk)
+            && let Some(payload) = slot::payload(extchunk)
+        {
+            slots.push(SlotReport {
+                offset: gif.offset(index),
+                chunk_type,
+                payload: key::inspect(&payload),
+            });
+        }
+    }
+    Ok(slots)
+}
+
+/// 所有块的原始字节，用于写入前的校验
+pub fn segments(bytes: &[u8]) -> Result<Vec<Segment>> {
+    let gif = GifRef::try_from(bytes)?;
+    let segments = gif
+        .chunks()
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| Segment::new(
+            gif.offset(index),
+            pngkey_slot(chunk).unwrap_or_else(|| chunk_name(chunk)),
+            gif.chunk_bytes(index),
+        ))
+        .collect();
+    Ok(segments)
+}
+
+/// 所有图像块的原始字节，包括图像描述符与局部颜色表
+pub fn pixel_data(bytes: &[u8]) -> Result<Vec<&[u8]>> {
+    let gif = GifRef::try_from(bytes)?;
+    let parts = gif
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| matches!(chunk, ChunkRef::Image(_)))
+        .map(|(index, _)| gif.chunk_bytes(index))
+        .collect();
+    Ok(parts)
+}
+
+/// 封印覆盖的关键块：逻辑屏幕描述符、全局颜色表、图形控制扩展与图像块。
+/// 图形控制扩展决定帧延迟、透明色与处置方式，改动后动画的显示也会变化
+pub fn critical_parts(bytes: &[u8]) -> Result<Vec<Part>> {
+    let gif = GifRef::try_from(bytes)?;
+    let parts = gif
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| {
+            matches!(
+                chunk,
+                ChunkRef::LogicalScreenDescriptor(_)
+                    | ChunkRef::GlobalColorTable(_)
+                    | ChunkRef::Image(_)
+                    | ChunkRef::Extension(ExtensionRef { extension_type: 0xF9, .. })
+            )
+        })
+        .map(|(index, chunk)| Part::new(chunk_name(chunk), gif.chunk_bytes(index)))
+        .collect();
+    Ok(parts)
+}
+
+/// 按策略删除元数据块，GIF没有EXIF，只需删除
+pub fn strip(args: &StripArgs, bytes: &[u8]) -> Result<(Vec<u8>, Vec<Stripped>)> {
+    let view = GifRef::try_from(bytes)?;
+
+    let stripped: Vec<Stripped> = view
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let category = category(chunk).filter(|&c| strip::removes(args, c))?;
+            Some(Stripped { index, category, replacement: None })
+        })
+        .collect();
+    let mut gif = view.to_gif();
+    for item in stripped.iter().rev() {
+        gif.remove_chunk_at(item.index);
+    }
+
+    Ok((gif.as_bytes()?, stripped))
+}
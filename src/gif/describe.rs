@@ -0,0 +1,111 @@
+use super::chunk::*;
+use crate::report::Fields;
+
+fn describe_graphic_control(data: &[u8]) -> Fields {
+    if data.len() < 5 {
+        return vec![("Data", "<Invalid Graphic Control>".to_owned())];
+    }
+    let packed = data[1];
+    let disposal = match (packed >> 2) & 0x07 {
+        0 => "Unspecified",
+        1 => "Do not dispose",
+        2 => "Restore to background",
+        3 => "Restore to previous",
+        _ => "Reserved",
+    };
+    let delay = u16::from_le_bytes([data[2], data[3]]);
+    let mut fields = vec![
+        ("Disposal method", disposal.to_owned()),
+        ("User input", (packed & 0x02 != 0).to_string()),
+    ];
+    if packed & 0x01 != 0 {
+        fields.push(("Transparent color index", data[4].to_string()));
+    }
+    fields.push(("Delay", format!("{} ms", delay as u32 * 10)));
+    fields
+}
+
+fn describe_application(extension: &ExtensionRef) -> Fields {
+    let (Some(identifier), Some(auth_code)) = (extension.application_identifier(), extension.application_auth_code()) else {
+        return vec![("Data", "<Invalid Application Extension>".to_owned())];
+    };
+    let mut fields = vec![
+        ("Application Identifier", identifier.iter().map(|&b| b as char).collect::<String>()),
+        ("Application Authentication Code", String::from_utf8_lossy(auth_code).to_string()),
+    ];
+    let data = sub_block_data(&extension.data[12..]);
+    let is_loop = (identifier == b"NETSCAPE" && auth_code == b"2.0") || (identifier == b"ANIMEXTS" && auth_code == b"1.0");
+    if is_loop && data.len() >= 3 && data[0] == 1 {
+        let loops = u16::from_le_bytes([data[1], data[2]]);
+        if loops == 0 {
+            fields.push(("Loop count", "infinite".to_owned()));
+        } else {
+            fields.push(("Loop count", loops.to_string()));
+        }
+    } else if identifier == b" pngkey " {
+        fields.push(("Data", format!("[{}]", String::from_utf8_lossy(&data))));
+    } else {
+        fields.push(("Data", format!("<{} bytes>", data.len())));
+    }
+    fields
+}
+
+/// 解析出扩展块中有意义的字段
+pub fn describe_extension(extension: &ExtensionRef) -> Fields {
+    let data = extension.data;
+    match extension.extension_type {
+        0xf9 => describe_graphic_control(data),
+        0xff => describe_application(extension),
+        0xfe => vec![("Comment", String::from_utf8_lossy(&sub_block_data(data)).to_string())],
+        0x01 if data.len() >= 13 => {
+            let value = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+            vec![
+                ("Grid", format!("{}x{} at {}, {}", value(5), value(7), value(1), value(3))),
+                ("Text", String::from_utf8_lossy(&sub_block_data(&data[13..])).to_string()),
+            ]
+        }
+        _ => vec![("Data", format!("<{} bytes>", sub_block_data(data).len()))],
+    }
+}
+
+/// 解析出块中有意义的字段
+pub fn describe(chunk: &ChunkRef) -> Fields {
+    match chunk {
+        ChunkRef::Header(data) => vec![("Signature", String::from_utf8_lossy(*data).to_string())],
+        ChunkRef::LogicalScreenDescriptor(lsd) => {
+            let global_color_table = if lsd.packed_fields & 0x80 != 0 {
+                format!("{} colors", color_table_size(lsd.packed_fields))
+            } else {
+                "none".to_owned()
+            };
+            vec![
+                ("Width", lsd.width.to_string()),
+                ("Height", lsd.height.to_string()),
+                ("Global color table", global_color_table),
+                ("Color resolution", format!("{} bits", ((lsd.packed_fields >> 4) & 0x07) + 1)),
+                ("Sorted", (lsd.packed_fields & 0x08 != 0).to_string()),
+                ("Background color index", lsd.background_color_index.to_string()),
+                ("Pixel aspect ratio", lsd.pixel_aspect_ratio.to_string()),
+            ]
+        }
+        ChunkRef::GlobalColorTable(data) => vec![("Colors", (data.len() / 3).to_string())],
+        ChunkRef::Image(image) => {
+            let descriptor = &image.descriptor;
+            let mut fields = vec![
+                ("Left", descriptor.left.to_string()),
+                ("Top", descriptor.top.to_string()),
+                ("Width", descriptor.width.to_string()),
+                ("Height", descriptor.height.to_string()),
+                ("Interlaced", (descriptor.packed_fields & 0x40 != 0).to_string()),
+            ];
+            if let Some(lct) = image.local_color_table {
+                fields.push(("Local color table", format!("{} colors", lct.len() / 3)));
+            }
+            fields.push(("LZW minimum code size", image.image_data[0].to_string()));
+            fields.push(("Image", format!("<Image Data, {} bytes>", sub_block_data(&image.image_data[1..]).len())));
+            fields
+        }
+        ChunkRef::Extension(extension) => describe_extension(extension),
+        ChunkRef::Trailer => vec![],
+    }
+}
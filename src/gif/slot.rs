@@ -0,0 +1,81 @@
+use super::chunk::{ExtensionRef, sub_block_data};
+use super::Gif;
+use crate::slot;
+use crate::Result;
+
+/// 命名槽位使用的认证码，数据以名称头开始
+pub const NAMED_AUTH_CODE: &[u8; 3] = b"NAM";
+
+/// pngkey写入的Application Extension的认证码
+fn auth_code<'a>(extension: &ExtensionRef<'a>) -> Option<&'a [u8]> {
+    if extension.application_identifier()? != Gif::IDENTIFIER {
+        return None;
+    }
+    extension.application_auth_code()
+}
+
+/// pngkey写入的扩展块中认证码之后的子块，不是pngkey写入的块返回None
+fn sub_blocks<'a>(extension: &ExtensionRef<'a>) -> Option<(&'a [u8], &'a [u8])> {
+    let auth_code = auth_code(extension)?;
+    Some((auth_code, extension.data.get(12..)?))
+}
+
+/// 扩展块在命令行中的类型：命名槽位为name:<名称>，其余为认证码，不是pngkey写入的块返回None
+pub fn slot_type(extension: &ExtensionRef) -> Option<String> {
+    let (auth_code, blocks) = sub_blocks(extension)?;
+    if auth_code == NAMED_AUTH_CODE
+        && let Some((name, _)) = slot::split(&sub_block_data(blocks))
+    {
+        return Some(slot::chunk_type(name));
+    }
+    Some(String::from_utf8_lossy(auth_code).to_string())
+}
+
+/// 扩展块中的载荷，命名槽位去掉名称头，不是pngkey写入的块返回None
+pub fn payload(extension: &ExtensionRef) -> Option<Vec<u8>> {
+    let (auth_code, blocks) = sub_blocks(extension)?;
+    let data = sub_block_data(blocks);
+    if auth_code == NAMED_AUTH_CODE
+        && let Some((_, payload)) = slot::split(&data)
+    {
+        return Some(payload.to_vec());
+    }
+    Some(data)
+}
+
+/// 槽位对应的认证码与数据，块类型不是3字节的认证码时返回错误
+pub fn chunk_data(chunk_type: &str, payload: &[u8]) -> Result<([u8; 3], Vec<u8>)> {
+    match slot::name(chunk_type) {
+        Some(name) => Ok((*NAMED_AUTH_CODE, slot::with_header(name, payload))),
+        None => {
+            let auth_code = chunk_type.as_bytes().try_into().map_err(|_| "Invalid ChunkType, should be 3 bytes long.")?;
+            Ok((auth_code, payload.to_vec()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_extensions_are_not_slots() {
+        let data = [&[11][..], &Gif::IDENTIFIER[..], b"ab"].concat();
+        let extension = ExtensionRef { extension_type: 0xff, data: &data };
+        assert_eq!(slot_type(&extension), None);
+        assert_eq!(payload(&extension), None);
+
+        let data = [&[11][..], &Gif::IDENTIFIER[..], b"abc", &[2, b'h', b'i', 0]].concat();
+        let extension = ExtensionRef { extension_type: 0xff, data: &data };
+        assert_eq!(slot_type(&extension).as_deref(), Some("abc"));
+        assert_eq!(payload(&extension).as_deref(), Some(&b"hi"[..]));
+    }
+
+    #[test]
+    fn chunk_data_checks_auth_code_length() {
+        assert_eq!(chunk_data("abc", b"hi").unwrap(), (*b"abc", b"hi".to_vec()));
+        assert!(chunk_data("ab", b"hi").is_err());
+        assert!(chunk_data("abcd", b"hi").is_err());
+        assert_eq!(chunk_data("name:a", b"hi").unwrap().0, *NAMED_AUTH_CODE);
+    }
+}
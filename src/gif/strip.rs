@@ -0,0 +1,71 @@
+use super::chunk::ChunkRef;
+use crate::args::StripCategory;
+
+/// 块所属的元数据类别，显示图像所必需的块返回None
+pub fn category(chunk: &ChunkRef) -> Option<StripCategory> {
+    let ChunkRef::Extension(extchunk) = chunk else {
+        return None;
+    };
+    let category = match extchunk.extension_type {
+        // 图形控制与纯文本扩展会影响显示的画面
+        0xF9 | 0x01 => return None,
+        0xFE => StripCategory::Comment,
+        0xFF => match (extchunk.application_identifier(), extchunk.application_auth_code()) {
+            // 循环次数
+            (Some(b"NETSCAPE"), Some(b"2.0")) | (Some(b"ANIMEXTS"), Some(b"1.0")) => return None,
+            (Some(b"XMP Data"), Some(b"XMP")) => StripCategory::Xmp,
+            (Some(b"ICCRGBG1"), Some(b"012")) => StripCategory::Icc,
+            _ => StripCategory::Private,
+        },
+        // 未知的扩展可能同样影响显示，保留
+        _ => return None,
+    };
+    Some(category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::command;
+    use crate::args::{StripArgs, StripCategory};
+    use crate::fixtures::{self, gif_extension};
+
+    fn comment() -> Vec<u8> {
+        gif_extension(0xFE, b"hi")
+    }
+
+    /// 循环次数与纯文本扩展需要保留
+    fn kept() -> Vec<Vec<u8>> {
+        vec![gif_extension(0xFF, b"NETSCAPE2.0"), gif_extension(0x01, &[0; 12])]
+    }
+
+    fn gif() -> Vec<u8> {
+        fixtures::gif_with(&[
+            vec![comment(), gif_extension(0xFF, b"XMP DataXMP"), gif_extension(0xFF, b"ICCRGBG1012")],
+            kept(),
+            vec![gif_extension(0xFF, b" pngkey abc")],
+        ].concat())
+    }
+
+    fn strip(args: &StripArgs) -> (Vec<u8>, Vec<StripCategory>) {
+        let (result, stripped) = command::strip(args, &gif()).unwrap();
+        (result, stripped.iter().map(|item| item.category).collect())
+    }
+
+    #[test]
+    fn strips_every_category() {
+        let (result, categories) = strip(&StripArgs::default());
+        assert_eq!(result, fixtures::gif_with(&kept()));
+        assert_eq!(categories, [StripCategory::Comment, StripCategory::Xmp, StripCategory::Icc, StripCategory::Private]);
+    }
+
+    #[test]
+    fn keeps_and_drops_categories() {
+        let args = StripArgs { keep: vec![StripCategory::Comment], ..Default::default() };
+        assert_eq!(strip(&args).0, fixtures::gif_with(&[vec![comment()], kept()].concat()));
+
+        let args = StripArgs { drop: vec![StripCategory::Comment], ..Default::default() };
+        let (result, categories) = strip(&args);
+        assert_eq!(categories, [StripCategory::Comment]);
+        assert_eq!(result.len(), gif().len() - comment().len());
+    }
+}
@@ -25,9 +25,10 @@ pub fn run_gui() -> Result<(), slint::PlatformError> {
             message: content.into(),
             output: v_output,
             password: v_key,
+            ..Default::default()
         };
         // println!("{:?}", encodeargs);
-        if let Err(e) = commands::encode(encodeargs) {
+        if let Err(e) = commands::encode(encodeargs, args::OutputFormat::Text) {
             eprintln!("Error: {}", e);
         }
     });
@@ -43,11 +44,12 @@ pub fn run_gui() -> Result<(), slint::PlatformError> {
             file_path: PathBuf::from(&file),
             chunk_type: chunk.into(),
             password: v_key,
+            ..Default::default()
         };
         // println!("{:?}", decodeargs);
         let ui = ui_weak.unwrap();
         ui.set_result_text("".into());
-        match commands::decode(decodeargs) {
+        match commands::decode(decodeargs, args::OutputFormat::Text) {
             Ok(content) => {
                 ui.set_result_text(content.into());
             }
@@ -61,9 +63,10 @@ pub fn run_gui() -> Result<(), slint::PlatformError> {
         let removeargs = args::RemoveArgs {
             file_path: PathBuf::from(&file),
             chunk_type: chunk.into(),
+            ..Default::default()
         };
         // println!("{:?}", removeargs);
-        if let Err(e) = commands::remove(removeargs) {
+        if let Err(e) = commands::remove(removeargs, args::OutputFormat::Text) {
             eprintln!("Error: {}", e);
         }
     });
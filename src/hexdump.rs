@@ -0,0 +1,38 @@
+/// 每行字节数
+const WIDTH: usize = 16;
+
+/// 经典的十六进制+ASCII格式，`base`为数据在文件中的偏移
+pub fn hexdump(data: &[u8], base: usize, limit: Option<usize>) -> String {
+    let shown = limit.map_or(data.len(), |limit| limit.min(data.len()));
+    let mut lines = Vec::new();
+    for (row, bytes) in data[..shown].chunks(WIDTH).enumerate() {
+        let mut hex = String::new();
+        for i in 0..WIDTH {
+            if i == WIDTH / 2 {
+                hex.push(' ');
+            }
+            match bytes.get(i) {
+                Some(byte) => hex.push_str(&format!("{:02x} ", byte)),
+                None => hex.push_str("   "),
+            }
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        lines.push(format!("{:08x}  {} |{}|", base + row * WIDTH, hex, ascii));
+    }
+    if shown < data.len() {
+        lines.push(format!("... ({} more bytes)", data.len() - shown));
+    }
+    lines.join("\n")
+}
+
+/// 输出块的位置与数据
+pub fn print_chunk(chunk_type: &str, offset: usize, length: usize, data: &[u8], data_offset: usize, limit: Option<usize>) {
+    println!("{} @ {:#010x}, length {}", chunk_type, offset, length);
+    if !data.is_empty() {
+        println!("{}", hexdump(data, data_offset, limit));
+    }
+    println!();
+}
@@ -1,10 +1,14 @@
 use std::convert::TryFrom;
 
 mod chunk;
+mod describe;
+mod stream;
+mod slot;
+mod strip;
 pub mod command;
 
 use crate::{Error, Result};
-use chunk::Chunk;
+use chunk::{Chunk, ChunkRef, has_length, is_entropy_coded};
 
 
 /// JPG结构
@@ -25,39 +29,36 @@ impl Jpg {
     /// 搜多特定chunk_type的chunk并移除
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         if let Some(index) = self.chunk_by_type(chunk_type) {
-            return Ok(self.chunks.remove(index));
+            Ok(self.chunks.remove(index))
         } else {
-            return Err(format!("JPG dose not contain chunk type {}", chunk_type).into());
+            Err(format!("JPG dose not contain chunk type {}", chunk_type).into())
         }
     }
 
-    /// 修改特定位置的chunk
-    pub fn modify_chunk(&mut self, index: usize, data: Vec<u8>) {
-        self.chunks[index].set_data(data);
+    /// 移除特定位置的chunk
+    pub fn remove_chunk_at(&mut self, index: usize) -> Chunk {
+        self.chunks.remove(index)
     }
 
-    pub fn chunks(&self) -> &[Chunk] {
-        &self.chunks
+    /// 修改特定位置的chunk
+    pub fn modify_chunk(&mut self, index: usize, data: Vec<u8>) -> Result<()> {
+        self.chunks[index].set_data(data)
     }
 
-    /// 找到第一个Chunk
+    /// 找到第一个Chunk，命名槽位按名称查找
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<usize> {
-        let chunk_type = u8::from_str_radix(chunk_type, 10).unwrap();
-        let index = self
+        self
             .chunks.iter()
-            .position(|chunk| chunk.chunk_type() == &chunk_type);
-        index
+            .position(|chunk| chunk.borrowed().slot_type() == chunk_type)
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+        let size = self.header.len() + self.chunks.iter().map(|chunk| chunk.data().len() + 12).sum::<usize>();
+        let mut bytes = Vec::with_capacity(size);
         bytes.extend(&self.header);
-        bytes.extend(
-            self.chunks
-               .iter()
-               .flat_map(|chunk| chunk.as_bytes())
-               .collect::<Vec<u8>>(),
-        );
+        for chunk in &self.chunks {
+            chunk.write_to(&mut bytes);
+        }
         bytes
     }
 }
@@ -85,42 +86,120 @@ impl TryFrom<&[u8]> for Jpg {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Jpg> {
-        let header: [u8; 2] = [bytes[0], bytes[1]];
+        Ok(JpgRef::try_from(bytes)?.to_jpg())
+    }
+}
+
+/// 借用源数据的JPG结构，decode、print、scan等只读操作不复制块数据
+pub struct JpgRef<'a> {
+    bytes: &'a [u8],
+    chunks: Vec<ChunkRef<'a>>,
+}
+
+impl<'a> JpgRef<'a> {
+    pub fn chunks(&self) -> &[ChunkRef<'a>] {
+        &self.chunks
+    }
+
+    /// 块在源文件中的原始字节
+    pub fn chunk_bytes(&self, chunk: &ChunkRef) -> &'a [u8] {
+        let offset = chunk.offset().unwrap_or_default();
+        &self.bytes[offset..offset + chunk.size()]
+    }
+
+    /// 复制出可修改的JPG
+    pub fn to_jpg(&self) -> Jpg {
+        Jpg {
+            header: [self.bytes[0], self.bytes[1]],
+            chunks: self.chunks.iter().map(ChunkRef::to_chunk).collect(),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for JpgRef<'a> {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<JpgRef<'a>> {
+        if bytes.len() < 2 {
+            return Err("JPG file is too short".into());
+        }
         let mut chunks = Vec::new();
         let mut index = 2;
         while index < bytes.len() {
+            let start = index;
+            if index + 2 > bytes.len() {
+                return Err("JPG chunk is too long".into());
+            }
             let marker_type = bytes[index + 1];
             index += 2;
 
-            match marker_type {
-                0xD8 => { // SOI
-                    chunks.push(Chunk::new(0xD8, (&[]).to_vec()));
-                }
-                0xD9 => { // EOI
-                    chunks.push(Chunk::new(0xD9, (&[]).to_vec()));
-                    // JPG标准结束
+            let mut chunk = if is_entropy_coded(marker_type) {
+                // SOS与RSTn：找到下一个 marker
+                let Some(next_marker_pos) = find_next_marker(bytes, index) else {
+                    return Err("No marker found after SOS chunk".into());
+                };
+                let chunk_bytes = &bytes[index..next_marker_pos];
+                index = next_marker_pos;
+                if marker_type == 0xDA && chunk_bytes.len() < 2 {
+                    return Err("JPG SOS chunk is too short".into());
                 }
-                0xDA => { // SOS
-                    // 找到下一个 marker
-                    if let Some(next_marker_pos) = find_next_marker(bytes, index) {
-                        let chunk_bytes = &bytes[index..next_marker_pos];
-                        chunks.push(Chunk::new(0xDA, chunk_bytes.to_vec()));
-                        index = next_marker_pos;
-                    } else {
-                        return Err("No marker found after SOS chunk".into());
-                    }
+                ChunkRef::new(marker_type, chunk_bytes)?
+            } else if !has_length(marker_type) {
+                // SOI与EOI
+                ChunkRef::new(marker_type, &[])?
+            } else {
+                if index + 2 > bytes.len() {
+                    return Err("JPG chunk is too long".into());
                 }
-                _ => {
-                    let length_bytes: [u8; 2] = bytes[index..index+2].try_into().unwrap();
-                    let length = u16::from_be_bytes(length_bytes) as usize;
-                    let chunk_end = index + length;
-                    let chunk_bytes = &bytes[index+2..chunk_end];
-                    chunks.push(Chunk::new(marker_type, chunk_bytes.to_vec()));
-                    index = chunk_end;
+                let length_bytes: [u8; 2] = bytes[index..index+2].try_into().unwrap();
+                let length = u16::from_be_bytes(length_bytes) as usize;
+                let chunk_end = index + length;
+                if length < 2 || chunk_end > bytes.len() {
+                    return Err("JPG chunk is too long".into());
                 }
-            }
-
+                let chunk_bytes = &bytes[index+2..chunk_end];
+                index = chunk_end;
+                ChunkRef::new(marker_type, chunk_bytes)?
+            };
+            chunk.set_offset(start);
+            chunks.push(chunk);
         }
-        Ok(Jpg { header, chunks })
+        Ok(JpgRef { bytes, chunks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// 带重启间隔的最小JPG：DRI、SOS以及两个RSTn之间的熵编码数据
+    const RESTART_JPG: &[u8] = &[
+        0xFF, 0xD8,
+        0xFF, 0xDD, 0x00, 0x04, 0x00, 0x01,
+        0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00, 0x12, 0xFF, 0x00, 0x34,
+        0xFF, 0xD0, 0x56, 0x78,
+        0xFF, 0xD1, 0x9A,
+        0xFF, 0xD9,
+    ];
+
+    #[test]
+    fn restart_markers_are_entropy_coded() {
+        let jpg = JpgRef::try_from(RESTART_JPG).unwrap();
+        let markers: Vec<u8> = jpg.chunks().iter().map(|chunk| *chunk.chunk_type()).collect();
+        assert_eq!(markers, [0xDD, 0xDA, 0xD0, 0xD1, 0xD9]);
+        assert_eq!(jpg.chunks()[2].data(), [0x56, 0x78]);
+        assert_eq!(jpg.to_jpg().as_bytes(), RESTART_JPG);
+    }
+
+    #[test]
+    fn memory_and_stream_parsers_agree() {
+        let memory = command::segments(RESTART_JPG).unwrap();
+        let (stream, _) = command::segments_stream(Cursor::new(RESTART_JPG), "").unwrap();
+        let describe = |segments: &[crate::verify::Segment]| -> Vec<(usize, String, usize, [u8; 32])> {
+            segments.iter().map(|s| (s.offset, s.chunk_type.clone(), s.length, s.digest)).collect()
+        };
+        assert_eq!(describe(&memory), describe(&stream));
     }
 }
@@ -2,6 +2,8 @@ use std::convert::TryFrom;
 use std::fmt;
 
 use crate::{Error, Result};
+use super::describe::{describe, marker_name};
+use super::slot;
 
 
 /// JPG块
@@ -11,57 +13,79 @@ pub struct Chunk {
     chunk_type: u8,
     length: u16,
     data: Vec<u8>,
+    /// 在源文件中的偏移，新建的块为None
+    offset: Option<usize>,
 }
 
-static AVOID_LENGTH_TYPE: [u8; 3] = [0xd8, 0xd9, 0xda];
+/// SOS与RSTn之后是熵编码数据，直到下一个标记。内存与流式解析共用
+pub fn is_entropy_coded(marker: u8) -> bool {
+    matches!(marker, 0xda | 0xd0..=0xd7)
+}
 
-impl Chunk {
-    pub fn new(chunk_type: u8, data: Vec<u8>) -> Chunk {
-        let length: u16;
-        if AVOID_LENGTH_TYPE.contains(&chunk_type) {
-            if chunk_type == 0xda {
-                // DA的长度放在数据里，这里只是显示头长度
-                length = u16::from_be_bytes([data[0], data[1]]);
-            } else {
-                length = 0;
-            }
+/// 标记之后是否有长度字段，SOI、EOI、SOS与RSTn没有
+pub fn has_length(marker: u8) -> bool {
+    !matches!(marker, 0xd8 | 0xd9) && !is_entropy_coded(marker)
+}
+
+/// 块头中的长度字段，数据超出一个块的最大长度时返回错误
+fn header_length(chunk_type: u8, data: &[u8]) -> Result<u16> {
+    if !has_length(chunk_type) {
+        if chunk_type == 0xda {
+            // DA的长度放在数据里，这里只是显示头长度
+            Ok(u16::from_be_bytes([data[0], data[1]]))
         } else {
-            length = u16::try_from(data.len()).unwrap() + 2;
+            Ok(0)
         }
-        Chunk {
+    } else {
+        u16::try_from(data.len() + 2).map_err(|_| "Payload is too long for a JPG chunk".into())
+    }
+}
+
+impl Chunk {
+    pub fn new(chunk_type: u8, data: Vec<u8>) -> Result<Chunk> {
+        Ok(Chunk {
             head: 0xff,
             chunk_type,
-            length,
+            length: header_length(chunk_type, &data)?,
             data,
-        }
-    }
-
-    pub fn length(&self) -> u16 {
-        self.length
-    }
-
-    pub fn chunk_type(&self) -> &u8 {
-        &self.chunk_type
+            offset: None,
+        })
     }
 
     pub fn data(&self) -> &[u8] {
         &self.data
     }
 
-    pub fn set_data(&mut self, data: Vec<u8>) {
-        self.length = u16::try_from(data.len()).unwrap() + 2;
+    pub fn set_data(&mut self, data: Vec<u8>) -> Result<()> {
+        self.length = header_length(self.chunk_type, &data)?;
         self.data = data;
+        Ok(())
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+        let mut bytes = Vec::with_capacity(self.data.len() + 4);
+        self.write_to(&mut bytes);
+        bytes
+    }
+
+    /// 追加到已有的缓冲区，避免为每个块单独分配
+    pub fn write_to(&self, bytes: &mut Vec<u8>) {
         bytes.extend(&self.head.to_be_bytes());
         bytes.extend(&self.chunk_type.to_be_bytes());
-        if !AVOID_LENGTH_TYPE.contains(&self.chunk_type) {
+        if has_length(self.chunk_type) {
             bytes.extend(&self.length.to_be_bytes());
         }
         bytes.extend(&self.data);
-        bytes
+    }
+
+    /// 借用为只读视图
+    pub fn borrowed(&self) -> ChunkRef<'_> {
+        ChunkRef {
+            chunk_type: self.chunk_type,
+            length: self.length,
+            data: &self.data,
+            offset: self.offset,
+        }
     }
 }
 
@@ -78,26 +102,124 @@ impl TryFrom<&[u8]> for Chunk {
         }
         let chunk_type = value[1];
         let data = value[4..].to_vec();
-        let chunk = Chunk::new(chunk_type, data);
-        Ok(chunk)
+        Chunk::new(chunk_type, data)
     }
 }
 
-static AVOID_TYPE: [u8; 2] = [0xc4, 0xda];
 impl fmt::Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let data: String;
-        let chunk_type_name = self.chunk_type;
-        if AVOID_TYPE.contains(&chunk_type_name) {
-            data = format!("<{:02X} DATA>", chunk_type_name);
-        } else {
-            data = String::from_utf8_lossy(self.data()).to_string();
+        fmt::Display::fmt(&self.borrowed(), f)
+    }
+}
+
+/// 借用源数据的JPG块，只读操作不复制块数据
+#[derive(Debug, Clone)]
+pub struct ChunkRef<'a> {
+    chunk_type: u8,
+    length: u16,
+    data: &'a [u8],
+    /// 在源文件中的偏移，新建的块为None
+    offset: Option<usize>,
+}
+
+impl<'a> ChunkRef<'a> {
+    pub fn new(chunk_type: u8, data: &'a [u8]) -> Result<ChunkRef<'a>> {
+        Ok(ChunkRef {
+            chunk_type,
+            length: header_length(chunk_type, data)?,
+            data,
+            offset: None,
+        })
+    }
+
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn chunk_type(&self) -> &u8 {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: usize) {
+        self.offset = Some(offset);
+    }
+
+    /// 数据部分相对于块开头的偏移
+    pub fn data_offset(&self) -> usize {
+        if has_length(self.chunk_type) { 4 } else { 2 }
+    }
+
+    /// 块在文件中占用的全部字节数
+    pub fn size(&self) -> usize {
+        self.data_offset() + self.data.len()
+    }
+
+    /// 块在命令行中的类型，命名槽位为name:<名称>
+    pub fn slot_type(&self) -> String {
+        slot::slot_type(self.chunk_type, self.data)
+    }
+
+    /// 块中的载荷，命名槽位去掉标识与名称头
+    pub fn payload(&self) -> &'a [u8] {
+        slot::payload(self.chunk_type, self.data)
+    }
+
+    /// 复制出可修改的块
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk {
+            head: 0xff,
+            chunk_type: self.chunk_type,
+            length: self.length,
+            data: self.data.to_vec(),
+            offset: self.offset,
         }
+    }
+}
+
+impl fmt::Display for ChunkRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = marker_name(self.chunk_type);
         writeln!(f, "Chunk {{")?;
-        writeln!(f, "  Lenghth: {}", self.length())?;
-        writeln!(f, "  Type: {}", self.chunk_type())?;
-        writeln!(f, "  Data: {}", data)?;
+        writeln!(f, "  Length: {}", self.length())?;
+        if name.is_empty() {
+            writeln!(f, "  Type: {}", self.chunk_type())?;
+        } else {
+            writeln!(f, "  Type: {} ({})", self.chunk_type(), name)?;
+        }
+        if let Some(name) = crate::slot::name(&self.slot_type()) {
+            writeln!(f, "  Slot: {}", name)?;
+        }
+        if let Some(fields) = describe(self) {
+            for (name, value) in fields {
+                writeln!(f, "  {}: {}", name, value)?;
+            }
+        } else {
+            writeln!(f, "  Data: {}", String::from_utf8_lossy(self.data()))?;
+        }
         writeln!(f, "}}")?;
         Ok(())
-    } 
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversize_segment_is_an_error() {
+        let mut chunk = Chunk::new(0xe1, vec![0; u16::MAX as usize - 2]).unwrap();
+        assert_eq!(chunk.borrowed().length(), u16::MAX);
+        assert!(Chunk::new(0xe1, vec![0; u16::MAX as usize - 1]).is_err());
+        assert!(chunk.set_data(vec![0; u16::MAX as usize]).is_err());
+        assert!(chunk.set_data(vec![0; 10]).is_ok());
+        assert_eq!(chunk.borrowed().length(), 12);
+    }
 }
@@ -1,99 +1,255 @@
-use std::fs;
+use std::io::{BufRead, Write};
 use std::str::FromStr;
 
 use crate::args::*;
-use super::chunk::Chunk;
-use super::Jpg;
+use super::chunk::{Chunk, ChunkRef, is_entropy_coded};
+use super::strip::category;
+use super::describe::{describe, marker_name};
+use super::{Jpg, JpgRef};
+use super::slot;
+use super::stream;
 use crate::Result;
+use crate::hexdump;
 use crate::key;
+use crate::report::{ChunkReport, SlotReport};
+use crate::seal::Part;
+use crate::verify::Segment;
+use crate::strip::{self, Stripped};
+use crate::stream::Rewrite;
 
 
 fn is_valid_chunk_type(chunk_type_str: &str) -> bool {
+    if crate::slot::name(chunk_type_str).is_some() {
+        return true;
+    }
     let chunk_type_u8 = u8::from_str(chunk_type_str).unwrap_or(0);
-    0x01 <= chunk_type_u8 && chunk_type_u8 <= 0xbf
+    (0x01..=0xbf).contains(&chunk_type_u8)
 }
 
-pub fn encode(args: EncodeArgs, bytes: &Vec<u8>) -> Result<()> {
-    if !is_valid_chunk_type(&args.chunk_type) {
-        return Err("Invalid ChunkType, should 1<= chunk-type <= 191.".into());
+/// 常见APPn块的标识
+static KNOWN_APP_SIGNATURES: [&[u8]; 10] = [
+    b"JFIF\0", b"JFXX\0", b"Exif\0", b"http://ns.adobe.com/", b"ICC_PROFILE\0",
+    b"Adobe", b"Ducky", b"Photoshop 3.0\0", b"MPF\0", b"FPXR\0",
+];
+
+/// 非标准块，即pngkey可能写入的块
+pub fn is_candidate_slot(chunk: &ChunkRef) -> bool {
+    match *chunk.chunk_type() {
+        0x01..=0xbf => true,
+        0xe0..=0xef => !KNOWN_APP_SIGNATURES.iter().any(|sig| chunk.data().starts_with(sig)),
+        _ => false,
     }
+}
 
-    // 密钥与信息
-    let password = args.password.unwrap_or_else(|| "".to_string());
-    let message = args.message;
-    let encrypted_message: String;
-    if !password.is_empty() {
-        encrypted_message = key::encrypt(&message, &password)?;
-    } else {
-        encrypted_message = message;
+pub fn encode(args: &EncodeArgs, bytes: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+    if !is_valid_chunk_type(&args.chunk_type) {
+        return Err("Invalid ChunkType, should 1<= chunk-type <= 191.".into());
     }
 
-    let mut jpg = Jpg::try_from(bytes.as_slice())?;
+    let mut jpg = Jpg::try_from(bytes)?;
+    let (marker, data) = slot::chunk_data(&args.chunk_type, payload)?;
 
     if let Some(index) = jpg.chunk_by_type(&args.chunk_type) {
-        jpg.modify_chunk(index, encrypted_message.as_bytes().to_vec());
+        jpg.modify_chunk(index, data)?;
     } else {
         let new_chunk = Chunk::new(
-            u8::from_str(&args.chunk_type).unwrap(),
-            encrypted_message.as_bytes().to_vec(),
-        );
+            marker,
+            data,
+        )?;
         jpg.append_chunk(new_chunk);
     }
 
-    if let Some(output_path) = args.output {
-        fs::write(output_path, jpg.as_bytes())?;
-    } else {
-        fs::write(args.file_path, jpg.as_bytes())?;
-    }
-
-    Ok(())
+    Ok(jpg.as_bytes())
 }
 
-pub fn decode(args: DecodeArgs, bytes: &Vec<u8>) -> Result<String> {
+pub fn decode(args: &DecodeArgs, bytes: &[u8]) -> Result<Vec<u8>> {
     if !is_valid_chunk_type(&args.chunk_type) {
         return Err("Invalid ChunkType, should 1<= chunk-type <= 191.".into());
     }
 
-    let jpg = Jpg::try_from(bytes.as_slice())?;
+    let jpg = JpgRef::try_from(bytes)?;
     let chunk = jpg
         .chunks()
         .iter()
-        .find(|chunk| chunk.chunk_type().to_string() == args.chunk_type)
+        .find(|chunk| chunk.slot_type() == args.chunk_type)
         .ok_or("Chunk not found")?;
-    let message = std::str::from_utf8(chunk.data()).expect("Invalid UTF-8");
-    let password = args.password.unwrap_or_else(|| "".to_string());
-    let decrypted_message = key::decrypt(&message, &password)?;
-    println!("{}", decrypted_message);
-    Ok(decrypted_message)
+    Ok(chunk.payload().to_vec())
 }
 
-pub fn remove(args: RemoveArgs, bytes: &Vec<u8>) -> Result<()> {
-    let mut jpg = Jpg::try_from(bytes.as_slice())?;
+/// 流式编码，只有载荷块读入内存
+pub fn encode_stream<R: BufRead, W: Write>(args: &EncodeArgs, reader: R, writer: W, payload: &[u8]) -> Result<Rewrite> {
+    if !is_valid_chunk_type(&args.chunk_type) {
+        return Err("Invalid ChunkType, should 1<= chunk-type <= 191.".into());
+    }
+    stream::rewrite(reader, writer, &args.chunk_type, Some(payload))
+}
+
+pub fn remove(args: &RemoveArgs, bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut jpg = Jpg::try_from(bytes)?;
     jpg.remove_chunk(&args.chunk_type)?;
-    fs::write(args.file_path, jpg.as_bytes())?;
-    Ok(())
+    Ok(jpg.as_bytes())
 }
 
-pub fn print(args: PrintArgs, bytes: &Vec<u8>) -> Result<()> {
+/// 流式删除
+pub fn remove_stream<R: BufRead, W: Write>(args: &RemoveArgs, reader: R, writer: W) -> Result<Rewrite> {
+    stream::rewrite(reader, writer, &args.chunk_type, None)
+}
+
+pub fn print(args: PrintArgs, bytes: &[u8]) -> Result<()> {
     let mut chunk_type: String = "".to_string();
     if let Some(ct) = args.chunk_type {
         chunk_type = ct;
     }
 
-    let jpg = Jpg::try_from(bytes.as_slice())?;
+    let jpg = JpgRef::try_from(bytes)?;
+    let show = |chunk: &ChunkRef| {
+        if args.hex {
+            let offset = chunk.offset().unwrap_or_default();
+            let data_offset = offset + chunk.data_offset();
+            let name = format!("{} ({})", chunk.chunk_type(), marker_name(*chunk.chunk_type()));
+            hexdump::print_chunk(&name, offset, chunk.size(), chunk.data(), data_offset, args.limit);
+        } else {
+            println!("{}", chunk);
+        }
+    };
 
     if chunk_type.is_empty() {
         for chunk in jpg.chunks() {
-            println!("{}", chunk);
+            show(chunk);
         }
     } else {
         let chunk = jpg
             .chunks()
             .iter()
-            .find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+            .find(|chunk| chunk.slot_type() == chunk_type)
             .ok_or("Chunk not found")?;
-        println!("{}", chunk);
+        show(chunk);
     }
 
     Ok(())
 }
+
+/// 与文本输出相同：按槽位类型匹配，指定块类型时只列出第一个匹配的块
+pub fn report(args: &PrintArgs, bytes: &[u8]) -> Result<Vec<ChunkReport>> {
+    let jpg = JpgRef::try_from(bytes)?;
+
+    let mut reports = Vec::new();
+    for (index, chunk) in jpg.chunks().iter().enumerate() {
+        let chunk_type = chunk.slot_type();
+        if args.chunk_type.as_ref().is_some_and(|ct| *ct != chunk_type) {
+            continue;
+        }
+        let payload = is_valid_chunk_type(&chunk_type).then(|| key::inspect(chunk.payload()));
+        let name = marker_name(*chunk.chunk_type());
+        let fields = describe(chunk)
+            .unwrap_or_else(|| vec![("Data", String::from_utf8_lossy(chunk.data()).to_string())]);
+        reports.push(ChunkReport {
+            index,
+            offset: chunk.offset().unwrap_or_default(),
+            length: chunk.size(),
+            data_length: chunk.data().len(),
+            chunk_type,
+            name: (!name.is_empty()).then_some(name),
+            fields,
+            payload,
+        });
+        if args.chunk_type.is_some() {
+            break;
+        }
+    }
+    if args.chunk_type.is_some() && reports.is_empty() {
+        return Err("Chunk not found".into());
+    }
+    Ok(reports)
+}
+
+pub fn scan(bytes: &[u8]) -> Result<Vec<SlotReport>> {
+    let jpg = JpgRef::try_from(bytes)?;
+    let slots = jpg
+        .chunks()
+        .iter()
+        .filter(|chunk| is_candidate_slot(chunk))
+        .map(|chunk| SlotReport {
+            offset: chunk.offset().unwrap_or_default(),
+            chunk_type: chunk.slot_type(),
+            payload: key::inspect(chunk.payload()),
+        })
+        .collect();
+    Ok(slots)
+}
+
+/// 所有块的原始字节，用于写入前的校验
+pub fn segments(bytes: &[u8]) -> Result<Vec<Segment>> {
+    let jpg = JpgRef::try_from(bytes)?;
+    let segments = jpg
+        .chunks()
+        .iter()
+        .map(|chunk| Segment::new(
+            chunk.offset().unwrap_or_default(),
+            chunk.slot_type(),
+            jpg.chunk_bytes(chunk),
+        ))
+        .collect();
+    Ok(segments)
+}
+
+/// 流式读取所有块的校验和与载荷，用于写入后的校验
+pub fn segments_stream<R: BufRead>(reader: R, chunk_type: &str) -> Result<(Vec<Segment>, Option<Vec<u8>>)> {
+    stream::segments(reader, chunk_type)
+}
+
+/// 扫描数据，即SOS与RSTn块的原始字节
+pub fn pixel_data(bytes: &[u8]) -> Result<Vec<&[u8]>> {
+    let jpg = JpgRef::try_from(bytes)?;
+    let parts = jpg
+        .chunks()
+        .iter()
+        .filter(|chunk| is_entropy_coded(*chunk.chunk_type()))
+        .map(|chunk| jpg.chunk_bytes(chunk))
+        .collect();
+    Ok(parts)
+}
+
+/// 封印覆盖的关键块：帧头、量化表、霍夫曼表、重启间隔与扫描数据
+pub fn critical_parts(bytes: &[u8]) -> Result<Vec<Part>> {
+    let jpg = JpgRef::try_from(bytes)?;
+    let parts = jpg
+        .chunks()
+        .iter()
+        .filter(|chunk| matches!(*chunk.chunk_type(), 0xC0..=0xCF | 0xDB | 0xDD) || is_entropy_coded(*chunk.chunk_type()))
+        .map(|chunk| Part::new(marker_name(*chunk.chunk_type()), jpg.chunk_bytes(chunk)))
+        .collect();
+    Ok(parts)
+}
+
+/// EXIF块的标识，之后为TIFF数据
+static EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// 按策略删除元数据块
+pub fn strip(args: &StripArgs, bytes: &[u8]) -> Result<(Vec<u8>, Vec<Stripped>)> {
+    let view = JpgRef::try_from(bytes)?;
+
+    let mut stripped = Vec::new();
+    for (index, chunk) in view.chunks().iter().enumerate() {
+        let Some(category) = category(chunk).filter(|&c| strip::removes(args, c)) else {
+            continue;
+        };
+        let replacement = (category == StripCategory::Exif)
+            .then(|| chunk.data().strip_prefix(EXIF_HEADER))
+            .flatten()
+            .and_then(|tiff| strip::reduce_exif(args, tiff))
+            .map(|tiff| [EXIF_HEADER, tiff.as_slice()].concat());
+        stripped.push(Stripped { index, category, replacement });
+    }
+    let mut jpg = view.to_jpg();
+    for item in stripped.iter().rev() {
+        match &item.replacement {
+            Some(data) => jpg.modify_chunk(item.index, data.clone())?,
+            None => {
+                jpg.remove_chunk_at(item.index);
+            }
+        }
+    }
+
+    Ok((jpg.as_bytes(), stripped))
+}
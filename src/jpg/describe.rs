@@ -0,0 +1,229 @@
+use super::chunk::ChunkRef;
+use crate::report::Fields;
+
+fn be_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// 标记名称
+pub fn marker_name(marker: u8) -> String {
+    let name = match marker {
+        0xC4 => "DHT",
+        0xC8 => "JPG",
+        0xCC => "DAC",
+        0xC0..=0xCF => return format!("SOF{}", marker - 0xC0),
+        0xD0..=0xD7 => return format!("RST{}", marker - 0xD0),
+        0xD8 => "SOI",
+        0xD9 => "EOI",
+        0xDA => "SOS",
+        0xDB => "DQT",
+        0xDC => "DNL",
+        0xDD => "DRI",
+        0xDE => "DHP",
+        0xDF => "EXP",
+        0xE0..=0xEF => return format!("APP{}", marker - 0xE0),
+        0xFE => "COM",
+        _ => "",
+    };
+    name.to_owned()
+}
+
+fn is_sof(marker: u8) -> bool {
+    (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker)
+}
+
+/// 以0结尾的标识符
+fn identifier(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
+
+fn describe_sof(marker: u8, data: &[u8]) -> Option<Fields> {
+    if data.len() < 6 {
+        return None;
+    }
+    let count = data[5] as usize;
+    if data.len() < 6 + count * 3 {
+        return None;
+    }
+    let process = match marker {
+        0xC0 => "Baseline",
+        0xC1 => "Extended sequential",
+        0xC2 => "Progressive",
+        0xC3 => "Lossless",
+        0xC5 => "Differential sequential",
+        0xC6 => "Differential progressive",
+        0xC7 => "Differential lossless",
+        0xC9 => "Extended sequential, arithmetic",
+        0xCA => "Progressive, arithmetic",
+        0xCB => "Lossless, arithmetic",
+        0xCD => "Differential sequential, arithmetic",
+        0xCE => "Differential progressive, arithmetic",
+        _ => "Differential lossless, arithmetic",
+    };
+    let components = data[6..6 + count * 3]
+        .chunks(3)
+        .map(|c| format!("#{} {}x{} Tq{}", c[0], c[1] >> 4, c[1] & 0x0f, c[2]))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(vec![
+        ("Process", process.to_owned()),
+        ("Precision", data[0].to_string()),
+        ("Height", be_u16(&data[1..3]).to_string()),
+        ("Width", be_u16(&data[3..5]).to_string()),
+        ("Components", format!("{} [{}]", count, components)),
+    ])
+}
+
+fn describe_dqt(data: &[u8]) -> Option<Fields> {
+    let mut tables = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let precision = data[pos] >> 4;
+        let id = data[pos] & 0x0f;
+        tables.push(format!("#{} {}-bit", id, if precision == 0 { 8 } else { 16 }));
+        pos += 1 + if precision == 0 { 64 } else { 128 };
+    }
+    if pos != data.len() {
+        return None;
+    }
+    Some(vec![("Tables", tables.join(", "))])
+}
+
+fn describe_dht(data: &[u8]) -> Option<Fields> {
+    let mut tables = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        if pos + 17 > data.len() {
+            return None;
+        }
+        let class = if data[pos] >> 4 == 0 { "DC" } else { "AC" };
+        let id = data[pos] & 0x0f;
+        let symbols: usize = data[pos + 1..pos + 17].iter().map(|&n| n as usize).sum();
+        tables.push(format!("{}#{} {} symbols", class, id, symbols));
+        pos += 17 + symbols;
+    }
+    if pos != data.len() {
+        return None;
+    }
+    Some(vec![("Tables", tables.join(", "))])
+}
+
+fn describe_sos(data: &[u8]) -> Option<Fields> {
+    // SOS块的数据包含了头部长度
+    if data.len() < 3 {
+        return None;
+    }
+    let header_length = be_u16(&data[0..2]) as usize;
+    let count = data[2] as usize;
+    if data.len() < header_length || header_length < 6 + count * 2 {
+        return None;
+    }
+    let components = data[3..3 + count * 2]
+        .chunks(2)
+        .map(|c| format!("#{} DC{} AC{}", c[0], c[1] >> 4, c[1] & 0x0f))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let spectral = &data[3 + count * 2..];
+    Some(vec![
+        ("Components", format!("{} [{}]", count, components)),
+        ("Spectral selection", format!("{}..{}", spectral[0], spectral[1])),
+        ("Successive approximation", format!("{}/{}", spectral[2] >> 4, spectral[2] & 0x0f)),
+        ("Data", format!("<Scan Data, {} bytes>", data.len() - header_length)),
+    ])
+}
+
+fn describe_app0(data: &[u8]) -> Option<Fields> {
+    if data.starts_with(b"JFIF\0") && data.len() >= 14 {
+        let units = match data[7] {
+            0 => "None",
+            1 => "DPI",
+            2 => "DPCM",
+            _ => "Unknown",
+        };
+        return Some(vec![
+            ("Identifier", "JFIF".to_owned()),
+            ("Version", format!("{}.{:02}", data[5], data[6])),
+            ("Density", format!("{}x{} {}", be_u16(&data[8..10]), be_u16(&data[10..12]), units)),
+            ("Thumbnail", format!("{}x{}", data[12], data[13])),
+        ]);
+    }
+    if data.starts_with(b"JFXX\0") {
+        return Some(vec![
+            ("Identifier", "JFXX".to_owned()),
+            ("Data", format!("<Thumbnail, {} bytes>", data.len() - 5)),
+        ]);
+    }
+    None
+}
+
+fn describe_app1(data: &[u8]) -> Option<Fields> {
+    if data.starts_with(b"Exif\0\0") && data.len() >= 14 {
+        let tiff = &data[6..];
+        let (order, ifd_offset) = match &tiff[0..2] {
+            b"II" => ("Little-endian", u32::from_le_bytes([tiff[4], tiff[5], tiff[6], tiff[7]])),
+            b"MM" => ("Big-endian", u32::from_be_bytes([tiff[4], tiff[5], tiff[6], tiff[7]])),
+            _ => return None,
+        };
+        let offset = ifd_offset as usize;
+        let mut fields = vec![
+            ("Identifier", "Exif".to_owned()),
+            ("Byte order", order.to_owned()),
+            ("IFD0 offset", ifd_offset.to_string()),
+        ];
+        if offset + 2 <= tiff.len() {
+            let entries = if order == "Little-endian" {
+                u16::from_le_bytes([tiff[offset], tiff[offset + 1]])
+            } else {
+                u16::from_be_bytes([tiff[offset], tiff[offset + 1]])
+            };
+            fields.push(("IFD0 entries", entries.to_string()));
+        }
+        fields.push(("Data", format!("<Exif, {} bytes>", data.len())));
+        return Some(fields);
+    }
+    if data.starts_with(b"http://ns.adobe.com/xap/1.0/\0") {
+        return Some(vec![
+            ("Identifier", "XMP".to_owned()),
+            ("Data", format!("<XMP, {} bytes>", data.len() - 29)),
+        ]);
+    }
+    None
+}
+
+fn describe_app(marker: u8, data: &[u8]) -> Fields {
+    let fields = match marker {
+        0xE0 => describe_app0(data),
+        0xE1 => describe_app1(data),
+        0xE2 if data.starts_with(b"ICC_PROFILE\0") && data.len() >= 14 => Some(vec![
+            ("Identifier", "ICC_PROFILE".to_owned()),
+            ("Sequence", format!("{}/{}", data[12], data[13])),
+            ("Data", format!("<ICC Profile, {} bytes>", data.len() - 14)),
+        ]),
+        _ => None,
+    };
+    fields.unwrap_or_else(|| {
+        vec![
+            ("Identifier", identifier(data)),
+            ("Data", format!("<APP{}, {} bytes>", marker - 0xE0, data.len())),
+        ]
+    })
+}
+
+/// 解析出块中有意义的字段，未知的块返回None
+pub fn describe(chunk: &ChunkRef) -> Option<Fields> {
+    let marker = *chunk.chunk_type();
+    let data = chunk.data();
+    match marker {
+        0xD8 | 0xD9 => Some(vec![]),
+        0xC4 => describe_dht(data),
+        0xDA => describe_sos(data),
+        0xD0..=0xD7 => Some(vec![("Data", format!("<Scan Data, {} bytes>", data.len()))]),
+        0xDB => describe_dqt(data),
+        0xDD if data.len() == 2 => Some(vec![("Restart interval", be_u16(data).to_string())]),
+        0xE0..=0xEF => Some(describe_app(marker, data)),
+        0xFE => Some(vec![("Comment", String::from_utf8_lossy(data).to_string())]),
+        _ if is_sof(marker) => describe_sof(marker, data),
+        _ => None,
+    }
+}
@@ -0,0 +1,37 @@
+use crate::Result;
+use crate::slot;
+
+/// 命名槽位使用APP15块
+pub const NAMED_MARKER: u8 = 0xEF;
+
+/// APP15块中pngkey的标识，之后为名称头
+static SIGNATURE: &[u8] = b"pngkey\0";
+
+/// 命名槽位的名称与载荷
+fn split(marker: u8, data: &[u8]) -> Option<(&str, &[u8])> {
+    if marker != NAMED_MARKER {
+        return None;
+    }
+    slot::split(data.strip_prefix(SIGNATURE)?)
+}
+
+/// 块在命令行中的类型：命名槽位为name:<名称>，其余为标记的十进制值
+pub fn slot_type(marker: u8, data: &[u8]) -> String {
+    match split(marker, data) {
+        Some((name, _)) => slot::chunk_type(name),
+        None => marker.to_string(),
+    }
+}
+
+/// 块中的载荷，命名槽位去掉标识与名称头
+pub fn payload(marker: u8, data: &[u8]) -> &[u8] {
+    split(marker, data).map_or(data, |(_, payload)| payload)
+}
+
+/// 槽位对应的标记与块数据
+pub fn chunk_data(chunk_type: &str, payload: &[u8]) -> Result<(u8, Vec<u8>)> {
+    match slot::name(chunk_type) {
+        Some(name) => Ok((NAMED_MARKER, [SIGNATURE, &slot::with_header(name, payload)].concat())),
+        None => Ok((chunk_type.parse::<u8>()?, payload.to_vec())),
+    }
+}
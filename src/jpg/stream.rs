@@ -0,0 +1,207 @@
+use std::io::{self, BufRead, Write};
+
+use super::chunk::{self, Chunk};
+use super::slot;
+use crate::Result;
+use crate::stream::{self, BUFFER_SIZE, ChunkCopy, Counter, Rewrite};
+use crate::verify::Segment;
+
+static TOO_LONG: &str = "JPG chunk is too long";
+
+/// 标记
+struct Header {
+    offset: usize,
+    marker: u8,
+    raw: [u8; 2],
+}
+
+/// 逐块读取JPG，块数据直接复制到输出而不读入内存
+struct SegmentReader<R> {
+    reader: R,
+    header: [u8; 2],
+    offset: usize,
+    /// 扫描熵编码数据时已经读到的下一个标记
+    pending: Option<Header>,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> SegmentReader<R> {
+    /// 读入命名槽位的整个块，块类型换成槽位类型
+    fn named(&mut self, header: &Header) -> Result<(Segment, Vec<u8>)> {
+        let mut bytes = Vec::new();
+        let mut copy = ChunkCopy::new(&mut bytes, true);
+        self.body(header, &mut copy)?;
+        let (segment, data) = copy.finish(header.offset, header.marker.to_string());
+        let chunk_type = slot::slot_type(header.marker, &data.unwrap_or_default());
+        Ok((Segment { chunk_type, ..segment }, bytes))
+    }
+
+    fn new(mut reader: R) -> Result<SegmentReader<R>> {
+        let mut header = [0u8; 2];
+        if stream::read_full(&mut reader, &mut header)? < 2 || header != [0xFF, 0xD8] {
+            return Err("Not a JPG file".into());
+        }
+        Ok(SegmentReader { reader, header, offset: 2, pending: None, buf: vec![0; BUFFER_SIZE] })
+    }
+
+    /// 下一个标记，文件结束时返回None
+    fn header(&mut self) -> Result<Option<Header>> {
+        if let Some(header) = self.pending.take() {
+            return Ok(Some(header));
+        }
+        let mut raw = [0u8; 2];
+        match stream::read_full(&mut self.reader, &mut raw)? {
+            0 => return Ok(None),
+            2 => {}
+            _ => return Err(TOO_LONG.into()),
+        }
+        if raw[0] != 0xFF {
+            return Err("Invalid chunk head".into());
+        }
+        let header = Header { offset: self.offset, marker: raw[1], raw };
+        self.offset += 2;
+        Ok(Some(header))
+    }
+
+    /// 复制块的全部字节到`copy`
+    fn body(&mut self, header: &Header, copy: &mut ChunkCopy) -> Result<()> {
+        copy.raw(&header.raw)?;
+        match header.marker {
+            marker if chunk::is_entropy_coded(marker) => self.entropy(copy),
+            marker if !chunk::has_length(marker) => Ok(()),
+            _ => {
+                let mut length = [0u8; 2];
+                stream::read_exact_or(&mut self.reader, &mut length, TOO_LONG)?;
+                copy.raw(&length)?;
+                let length = u16::from_be_bytes(length) as usize;
+                if length < 2 {
+                    return Err("Invalid JPG chunk length".into());
+                }
+                copy.copy_data(&mut self.reader, length - 2, &mut self.buf, TOO_LONG)?;
+                self.offset += length;
+                Ok(())
+            }
+        }
+    }
+
+    /// 复制熵编码数据，遇到0xFF后不是0x00的字节时停止，该标记留给下一个块
+    fn entropy(&mut self, copy: &mut ChunkCopy) -> Result<()> {
+        let mut pending_ff = false;
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Err("No marker found after SOS chunk".into());
+            }
+            if pending_ff {
+                let marker = buf[0];
+                if marker != 0x00 {
+                    self.reader.consume(1);
+                    self.pending = Some(Header { offset: self.offset - 1, marker, raw: [0xFF, marker] });
+                    self.offset += 1;
+                    return Ok(());
+                }
+                copy.data(&[0xFF])?;
+                pending_ff = false;
+                continue;
+            }
+            let (n, found) = match buf.iter().position(|&b| b == 0xFF) {
+                Some(i) => (i, true),
+                None => (buf.len(), false),
+            };
+            copy.data(&buf[..n])?;
+            let consumed = n + usize::from(found);
+            self.reader.consume(consumed);
+            self.offset += consumed;
+            pending_ff = found;
+        }
+    }
+}
+
+fn write_chunk<W: Write>(writer: &mut Counter<W>, chunk_type: &str, payload: &[u8]) -> Result<Segment> {
+    let (marker, data) = slot::chunk_data(chunk_type, payload)?;
+    let bytes = Chunk::new(marker, data)?.as_bytes();
+    let segment = Segment::new(writer.count, chunk_type.to_owned(), &bytes);
+    writer.write_all(&bytes)?;
+    Ok(segment)
+}
+
+/// 流式改写：替换第一个`chunk_type`块的数据，`payload`为None时删除该块，
+/// 没有该块时把新块追加到文件末尾。其余块原样复制，不读入内存
+pub fn rewrite<R: BufRead, W: Write>(reader: R, writer: W, chunk_type: &str, payload: Option<&[u8]>) -> Result<Rewrite> {
+    let mut reader = SegmentReader::new(reader)?;
+    let mut writer = Counter::new(writer);
+    writer.write_all(&reader.header)?;
+
+    let (mut before, mut after) = (Vec::new(), Vec::new());
+    let mut found = false;
+    let mut sink = io::sink();
+    while let Some(header) = reader.header()? {
+        // 命名槽位需要读入数据才能确定名称
+        let mut named = None;
+        if header.marker == slot::NAMED_MARKER {
+            named = Some(reader.named(&header)?);
+        }
+        let slot_type = named.as_ref().map_or_else(|| header.marker.to_string(), |(segment, _)| segment.chunk_type.clone());
+        if !found && slot_type == chunk_type {
+            found = true;
+            match named {
+                Some((segment, _)) => before.push(segment),
+                None => {
+                    let mut copy = ChunkCopy::new(&mut sink, false);
+                    reader.body(&header, &mut copy)?;
+                    before.push(copy.finish(header.offset, slot_type).0);
+                }
+            }
+            if let Some(payload) = payload {
+                after.push(write_chunk(&mut writer, chunk_type, payload)?);
+            }
+            continue;
+        }
+        let offset = writer.count;
+        let segment = match named {
+            Some((segment, bytes)) => {
+                writer.write_all(&bytes)?;
+                segment
+            }
+            None => {
+                let mut copy = ChunkCopy::new(&mut writer, false);
+                reader.body(&header, &mut copy)?;
+                copy.finish(header.offset, slot_type).0
+            }
+        };
+        after.push(Segment { offset, ..segment.clone() });
+        before.push(segment);
+    }
+    if !found {
+        let Some(payload) = payload else {
+            return Err(format!("JPG dose not contain chunk type {}", chunk_type).into());
+        };
+        after.push(write_chunk(&mut writer, chunk_type, payload)?);
+    }
+    writer.flush()?;
+
+    Ok(Rewrite { before, after, original_size: reader.offset, size: writer.count })
+}
+
+/// 流式读取所有块的校验和，只读入第一个`chunk_type`块与命名槽位的数据
+pub fn segments<R: BufRead>(reader: R, chunk_type: &str) -> Result<(Vec<Segment>, Option<Vec<u8>>)> {
+    let mut reader = SegmentReader::new(reader)?;
+    let mut segments = Vec::new();
+    let mut payload = None;
+    let mut sink = io::sink();
+    while let Some(header) = reader.header()? {
+        let named = header.marker == slot::NAMED_MARKER;
+        let keep = named || (payload.is_none() && header.marker.to_string() == chunk_type);
+        let mut copy = ChunkCopy::new(&mut sink, keep);
+        reader.body(&header, &mut copy)?;
+        let (mut segment, data) = copy.finish(header.offset, header.marker.to_string());
+        if let Some(data) = &data {
+            segment.chunk_type = slot::slot_type(header.marker, data);
+        }
+        if payload.is_none() && segment.chunk_type == chunk_type {
+            payload = data.map(|data| slot::payload(header.marker, &data).to_vec());
+        }
+        segments.push(segment);
+    }
+    Ok((segments, payload))
+}
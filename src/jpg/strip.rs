@@ -0,0 +1,88 @@
+use super::chunk::ChunkRef;
+use crate::args::StripCategory;
+
+/// 块所属的元数据类别，显示图像所必需的块返回None
+pub fn category(chunk: &ChunkRef) -> Option<StripCategory> {
+    let data = chunk.data();
+    let category = match *chunk.chunk_type() {
+        0xE0 if data.starts_with(b"JFIF\0") => return None,
+        // Adobe块决定CMYK/YCCK的颜色转换
+        0xEE if data.starts_with(b"Adobe") => return None,
+        0xE1 if data.starts_with(b"Exif\0") => StripCategory::Exif,
+        0xE1 if data.starts_with(b"http://ns.adobe.com/") => StripCategory::Xmp,
+        0xE2 if data.starts_with(b"ICC_PROFILE\0") => StripCategory::Icc,
+        0xE0..=0xEF if super::command::is_candidate_slot(chunk) => StripCategory::Private,
+        0xE0..=0xEF => StripCategory::Other,
+        0xFE => StripCategory::Comment,
+        0x01..=0xBF => StripCategory::Private,
+        _ => return None,
+    };
+    Some(category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::command;
+    use crate::args::{StripArgs, StripCategory};
+    use crate::fixtures::{self, jpg_segment};
+    use crate::strip;
+
+    fn exif(tiff: &[u8]) -> Vec<u8> {
+        jpg_segment(0xE1, &[&b"Exif\0\0"[..], tiff].concat())
+    }
+
+    fn comment() -> Vec<u8> {
+        jpg_segment(0xFE, b"hi")
+    }
+
+    fn adobe() -> Vec<u8> {
+        jpg_segment(0xEE, b"Adobe\0\x64\0\0\0\0\x01")
+    }
+
+    /// 每个类别各一个段，以及需要保留的Adobe段
+    fn jpg() -> Vec<u8> {
+        fixtures::jpg_with(&[
+            exif(&fixtures::exif(6)),
+            jpg_segment(0xE1, b"http://ns.adobe.com/xap/1.0/\0<x/>"),
+            jpg_segment(0xE2, b"ICC_PROFILE\0\x01\x01icc"),
+            jpg_segment(0xED, b"Photoshop 3.0\08BIM"),
+            adobe(),
+            comment(),
+            jpg_segment(0xE5, b"payload"),
+        ])
+    }
+
+    fn strip(args: &StripArgs) -> (Vec<u8>, Vec<StripCategory>) {
+        let (result, stripped) = command::strip(args, &jpg()).unwrap();
+        (result, stripped.iter().map(|item| item.category).collect())
+    }
+
+    #[test]
+    fn strips_every_category() {
+        let (result, categories) = strip(&StripArgs::default());
+        assert_eq!(result, fixtures::jpg_with(&[adobe()]));
+        assert_eq!(
+            categories,
+            [
+                StripCategory::Exif,
+                StripCategory::Xmp,
+                StripCategory::Icc,
+                StripCategory::Other,
+                StripCategory::Comment,
+                StripCategory::Private,
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_and_drops_categories() {
+        let args = StripArgs { keep: vec![StripCategory::Comment, StripCategory::Orientation], ..Default::default() };
+        let reduced = strip::reduce_exif(&args, &fixtures::exif(6)).unwrap();
+        assert_eq!(strip(&args).0, fixtures::jpg_with(&[exif(&reduced), adobe(), comment()]));
+
+        let args = StripArgs { drop: vec![StripCategory::Comment], ..Default::default() };
+        let (result, categories) = strip(&args);
+        assert_eq!(categories, [StripCategory::Comment]);
+        assert_eq!(result.len(), jpg().len() - comment().len());
+    }
+}
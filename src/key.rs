@@ -1,67 +1,623 @@
 use chacha20poly1305::{
-    aead::{Aead, KeyInit, OsRng},
-    ChaCha20Poly1305, Nonce
+    aead::{Aead, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, XChaCha20Poly1305
 };
+use aes_gcm_siv::Aes256GcmSiv;
 use argon2::{
     Argon2, password_hash::{PasswordHasher, SaltString, rand_core::RngCore}
 };
 use base64::{Engine as _, engine::general_purpose};
+use clap::ValueEnum;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
 
 use crate::Result;
 
-pub fn encrypt(plaintext: &str, password: &str) -> Result<String> {
-    // 生成随机salt
-    let salt = SaltString::generate(&mut OsRng);
-    
-    // 使用Argon2id派生密钥
-    let argon2 = Argon2::default();
-    let password_hash = argon2.hash_password(password.as_bytes(), &salt)?;
-    let key = password_hash.hash.unwrap();
-    
-    // 生成随机nonce（ChaCha20-Poly1305使用12字节nonce）
-    let mut nonce_bytes = [0u8; 12];
+/// 载荷信息
+#[derive(Debug, Clone, Serialize)]
+pub struct PayloadInfo {
+    pub encrypted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<&'static str>,
+    /// 密文绑定的范围：slot或image，旧格式的载荷没有绑定
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binding: Option<&'static str>,
+    /// 使用密钥文件加密时密钥的ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// 明文在加密前经过填充，密文长度不反映信息长度
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub padded: bool,
+    /// 载荷在块中占用的字节数
+    pub size: usize,
+}
+
+/// 密文头的版本标识
+static HEADER_VERSION: &str = "pk2";
+
+/// 密文头：pk2;ad=slot;cipher=...;kid=...;pad=1，字段以分号分隔，整个密文头也作为关联数据的一部分
+struct Header {
+    binding: Binding,
+    cipher: Cipher,
+    /// 使用密钥文件时记录密钥ID，此时没有salt
+    key_id: Option<String>,
+    /// 明文以4字节长度开头，之后为填充
+    padded: bool,
+}
+
+impl Header {
+    fn parse(text: &str) -> Result<Header> {
+        let mut fields = text.split(';');
+        if fields.next() != Some(HEADER_VERSION) {
+            return Err("Unsupported payload header".into());
+        }
+        let mut binding = None;
+        // 没有cipher字段的密文头来自只支持ChaCha20-Poly1305的版本
+        let mut cipher = Cipher::ChaCha20Poly1305;
+        let mut key_id = None;
+        let mut padded = false;
+        for field in fields {
+            match field.split_once('=') {
+                Some(("ad", "slot")) => binding = Some(Binding::Slot),
+                Some(("ad", "image")) => binding = Some(Binding::Image),
+                Some(("cipher", name)) => cipher = Cipher::from_name(name).ok_or_else(|| format!("Unsupported cipher {}", name))?,
+                Some(("kid", id)) => key_id = Some(id.to_owned()),
+                Some(("pad", "1")) => padded = true,
+                _ => return Err(format!("Unknown payload header field {}", field).into()),
+            }
+        }
+        Ok(Header { binding: binding.ok_or("Payload header has no binding")?, cipher, key_id, padded })
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{};ad={};cipher={}", HEADER_VERSION, self.binding.name(), self.cipher.name());
+        if let Some(id) = &self.key_id {
+            text.push_str(&format!(";kid={}", id));
+        }
+        if self.padded {
+            text.push_str(";pad=1");
+        }
+        text
+    }
+}
+
+/// The AEAD cipher used for encrypted payloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Cipher {
+    /// ChaCha20-Poly1305 with a 96-bit random nonce.
+    #[default]
+    #[value(name = "chacha20poly1305")]
+    ChaCha20Poly1305,
+    /// XChaCha20-Poly1305 with a 192-bit random nonce, safe for very large batches.
+    #[value(name = "xchacha20poly1305")]
+    XChaCha20Poly1305,
+    /// AES-256-GCM-SIV, nonce-misuse resistant.
+    #[value(name = "aes256gcmsiv")]
+    Aes256GcmSiv,
+}
+
+impl Cipher {
+    fn name(self) -> &'static str {
+        match self {
+            Cipher::ChaCha20Poly1305 => "chacha20poly1305",
+            Cipher::XChaCha20Poly1305 => "xchacha20poly1305",
+            Cipher::Aes256GcmSiv => "aes256gcmsiv",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Cipher> {
+        [Cipher::ChaCha20Poly1305, Cipher::XChaCha20Poly1305, Cipher::Aes256GcmSiv].into_iter().find(|cipher| cipher.name() == name)
+    }
+
+    fn nonce_len(self) -> usize {
+        match self {
+            Cipher::XChaCha20Poly1305 => 24,
+            Cipher::ChaCha20Poly1305 | Cipher::Aes256GcmSiv => 12,
+        }
+    }
+
+    fn encrypt(self, key: &[u8], nonce: &[u8], payload: Payload) -> Result<Vec<u8>> {
+        Ok(match self {
+            Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)?.encrypt(nonce.into(), payload)?,
+            Cipher::XChaCha20Poly1305 => XChaCha20Poly1305::new_from_slice(key)?.encrypt(nonce.into(), payload)?,
+            Cipher::Aes256GcmSiv => Aes256GcmSiv::new_from_slice(key)?.encrypt(nonce.into(), payload)?,
+        })
+    }
+
+    fn decrypt(self, key: &[u8], nonce: &[u8], payload: Payload) -> Result<Vec<u8>> {
+        Ok(match self {
+            Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)?.decrypt(nonce.into(), payload)?,
+            Cipher::XChaCha20Poly1305 => XChaCha20Poly1305::new_from_slice(key)?.decrypt(nonce.into(), payload)?,
+            Cipher::Aes256GcmSiv => Aes256GcmSiv::new_from_slice(key)?.decrypt(nonce.into(), payload)?,
+        })
+    }
+}
+
+/// 加密前把明文填充到的长度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Padding {
+    /// 不小于64字节的2的幂
+    Bucket,
+    /// 随机增加不超过信息本身长度的字节，至少可达256字节
+    Random,
+    /// 固定长度，信息更长时返回错误
+    Fixed(usize),
+}
+
+/// 命令行中固定填充长度的上限，填充前就要分配这么多内存
+const MAX_FIXED_PADDING: usize = 16 << 20;
+
+impl std::str::FromStr for Padding {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Padding, String> {
+        match text {
+            "bucket" => Ok(Padding::Bucket),
+            "random" => Ok(Padding::Random),
+            _ => match text.parse() {
+                Ok(size) if size <= MAX_FIXED_PADDING => Ok(Padding::Fixed(size)),
+                Ok(_) => Err(format!("Padding size {} is too large, at most {} bytes", text, MAX_FIXED_PADDING)),
+                Err(_) => Err(format!("Invalid padding {}, use bucket, random or a size in bytes", text)),
+            },
+        }
+    }
+}
+
+/// 长度前缀的字节数
+const LENGTH_PREFIX: usize = 4;
+
+impl Padding {
+    /// 以4字节大端长度开头，再用零填充到目标长度
+    fn pad(self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let framed = LENGTH_PREFIX + plaintext.len();
+        let size = match self {
+            Padding::Bucket => framed.next_power_of_two().max(64),
+            Padding::Random => framed + (OsRng.next_u64() as usize) % (framed.max(256) + 1),
+            Padding::Fixed(size) if size >= framed => size,
+            Padding::Fixed(size) => return Err(format!("Message needs {} bytes, more than the padding size {}", framed, size).into()),
+        };
+        let mut padded = Vec::with_capacity(size);
+        padded.extend_from_slice(&u32::try_from(plaintext.len())?.to_be_bytes());
+        padded.extend_from_slice(plaintext);
+        padded.resize(size, 0);
+        Ok(padded)
+    }
+
+    fn unpad(padded: &[u8]) -> Result<&[u8]> {
+        let (prefix, rest) = padded.split_first_chunk::<LENGTH_PREFIX>().ok_or("Invalid padding")?;
+        rest.get(..u32::from_be_bytes(*prefix) as usize).ok_or_else(|| "Invalid padding".into())
+    }
+}
+
+/// 密文绑定的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    /// 格式与块类型
+    Slot,
+    /// 格式、块类型与图像数据
+    Image,
+}
+
+impl Binding {
+    fn name(self) -> &'static str {
+        match self {
+            Binding::Slot => "slot",
+            Binding::Image => "image",
+        }
+    }
+}
+
+/// 载荷所在的位置，作为AEAD的关联数据，复制到其他图像或改名的块中无法解密
+pub struct Context<'a> {
+    pub format: &'a str,
+    pub chunk_type: &'a str,
+    /// 图像数据的摘要，只有绑定到图像时需要
+    pub image: Option<[u8; 32]>,
+}
+
+impl Context<'_> {
+    fn associated_data(&self, header: &Header, header_text: &str) -> Result<Vec<u8>> {
+        let mut data = [&b"pngkey\0"[..], header_text.as_bytes(), b"\0", self.format.as_bytes(), b"\0", self.chunk_type.as_bytes()].concat();
+        if header.binding == Binding::Image {
+            let image = self.image.ok_or("Image digest is required to decrypt this payload")?;
+            data.push(0);
+            data.extend_from_slice(&image);
+        }
+        Ok(data)
+    }
+}
+
+/// 判断载荷是否为加密格式：pk2;ad=...::salt::nonce::ciphertext，或旧格式salt::nonce::ciphertext
+pub fn inspect(payload: &[u8]) -> PayloadInfo {
+    let envelope = std::str::from_utf8(payload)
+        .ok()
+        .and_then(|text| parse(text).and_then(|envelope| envelope.ok()));
+    let key_id = envelope.as_ref().and_then(|envelope| envelope.key_id()).map(str::to_owned);
+    let header = envelope.as_ref().and_then(|envelope| envelope.header.as_ref());
+    PayloadInfo {
+        encrypted: envelope.is_some(),
+        kdf: envelope.as_ref().filter(|_| key_id.is_none()).map(|_| "argon2id"),
+        cipher: envelope.as_ref().map(|envelope| envelope.cipher().name()),
+        binding: header.map(|header| header.binding.name()),
+        key_id,
+        padded: header.is_some_and(|header| header.padded),
+        size: payload.len(),
+    }
+}
+
+/// 载荷是否绑定到图像数据，解密前需要计算图像摘要
+pub fn binds_image(encrypted: &str) -> bool {
+    matches!(parse(encrypted), Some(Ok(Envelope { header: Some(Header { binding: Binding::Image, .. }), .. })))
+}
+
+/// 加密载荷的填充：填充过的载荷按原来的长度固定填充，重新加密后长度不变
+pub fn padding(encrypted: &str) -> Option<Padding> {
+    let envelope = parse(encrypted)?.ok()?;
+    let padded = envelope.header.as_ref()?.padded;
+    // AEAD的认证标签为16字节
+    padded.then(|| Padding::Fixed(envelope.ciphertext.len().saturating_sub(16)))
+}
+
+/// 由密码派生密钥时使用的salt，非加密格式或使用密钥文件时返回None
+pub fn salt(encrypted: &str) -> Option<String> {
+    let envelope = parse(encrypted)?.ok()?;
+    (envelope.key_id().is_none() && !envelope.salt.is_empty()).then_some(envelope.salt)
+}
+
+/// 加密载荷使用的算法，非加密格式返回None
+pub fn cipher(encrypted: &str) -> Option<Cipher> {
+    parse(encrypted).and_then(|envelope| envelope.ok()).map(|envelope| envelope.cipher())
+}
+
+/// 由密钥计算ID：SHA-256的前8字节，不泄露密钥本身
+pub fn key_id(key: &[u8]) -> String {
+    Sha256::new().chain_update(b"pngkey-key-id\0").chain_update(key).finalize()[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 密钥的来源
+enum Source {
+    /// 由密码和salt经Argon2id派生
+    Password(SaltString),
+    /// 来自密钥文件，记录密钥ID
+    KeyFile(String),
+}
+
+/// 加密使用的密钥，由Argon2id派生或直接来自密钥文件
+pub struct DerivedKey {
+    source: Source,
+    key: Vec<u8>,
+}
+
+impl DerivedKey {
+    /// 生成随机salt并派生密钥
+    pub fn generate(password: &str) -> Result<DerivedKey> {
+        Self::derive(password, SaltString::generate(&mut OsRng))
+    }
+
+    /// 使用指定的salt派生密钥
+    pub fn derive(password: &str, salt: SaltString) -> Result<DerivedKey> {
+        let argon2 = Argon2::default();
+        let password_hash = argon2.hash_password(password.as_bytes(), &salt)?;
+        let key = password_hash.hash.ok_or("Argon2 produced no hash")?.as_bytes().to_vec();
+        Ok(DerivedKey { source: Source::Password(salt), key })
+    }
+
+    /// 直接使用256位密钥，不经过KDF
+    pub fn from_key(key: Vec<u8>) -> DerivedKey {
+        DerivedKey { source: Source::KeyFile(key_id(&key)), key }
+    }
+
+    /// 使用保存下来的salt重新派生密钥
+    pub fn from_salt(password: &str, salt: &str) -> Result<DerivedKey> {
+        Self::derive(password, SaltString::from_b64(salt)?)
+    }
+
+    /// 派生时使用的salt，密钥文件没有salt
+    pub fn salt(&self) -> Option<&str> {
+        match &self.source {
+            Source::Password(salt) => Some(salt.as_str()),
+            Source::KeyFile(_) => None,
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        match &self.source {
+            Source::Password(_) => None,
+            Source::KeyFile(id) => Some(id),
+        }
+    }
+
+    /// HMAC-SHA256，`domain`区分不同用途，避免同一个密钥的MAC被挪作他用
+    fn hmac(&self, domain: &str, data: &[u8]) -> Result<Hmac<Sha256>> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key)?;
+        mac.update(domain.as_bytes());
+        mac.update(&[0]);
+        mac.update(data);
+        Ok(mac)
+    }
+
+    pub fn mac(&self, domain: &str, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.hmac(domain, data)?.finalize().into_bytes().to_vec())
+    }
+
+    /// 以恒定时间比较MAC
+    pub fn verify_mac(&self, domain: &str, data: &[u8], tag: &[u8]) -> Result<()> {
+        self.hmac(domain, data)?.verify_slice(tag).map_err(|_| "MAC check failed, wrong password or the data was modified".into())
+    }
+}
+
+/// 密文的组成部分
+struct Envelope {
+    /// 旧格式没有密文头，也没有关联数据
+    header: Option<Header>,
+    header_text: String,
+    salt: String,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    fn cipher(&self) -> Cipher {
+        self.header.as_ref().map_or(Cipher::ChaCha20Poly1305, |header| header.cipher)
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        self.header.as_ref().and_then(|header| header.key_id.as_deref())
+    }
+}
+
+/// 拆分pk2;ad=...::salt::nonce::ciphertext或salt::nonce::ciphertext，非加密格式返回None
+fn parse(encrypted: &str) -> Option<Result<Envelope>> {
+    let mut parts: Vec<&str> = encrypted.split("::").collect();
+    let header_text = match parts.len() {
+        3 => None,
+        4 if parts[0].starts_with(HEADER_VERSION) => Some(parts.remove(0)),
+        _ => return None,
+    };
+    // 只有各部分都是Base64且nonce为12或24字节时才视为加密格式
+    let decoded: Vec<Vec<u8>> = parts.iter().map(|part| general_purpose::STANDARD.decode(part)).collect::<std::result::Result<_, _>>().ok()?;
+    if ![12, 24].contains(&decoded[1].len()) {
+        return None;
+    }
+    let envelope = || -> Result<Envelope> {
+        let header = header_text.map(Header::parse).transpose()?;
+        if decoded[1].len() != header.as_ref().map_or(Cipher::ChaCha20Poly1305, |header| header.cipher).nonce_len() {
+            return Err("Nonce length does not match the cipher".into());
+        }
+        Ok(Envelope {
+            header,
+            header_text: header_text.unwrap_or_default().to_owned(),
+            salt: String::from_utf8(decoded[0].clone())?,
+            nonce: decoded[1].clone(),
+            ciphertext: decoded[2].clone(),
+        })
+    };
+    Some(envelope())
+}
+
+/// 使用已派生的密钥加密，批量处理时可复用同一个密钥。
+/// 密文绑定到`context`中的格式与块类型，有图像摘要时还绑定到图像数据
+/// 指定`padding`时先填充明文，密文长度只反映填充后的长度
+pub fn encrypt_with(plaintext: &str, key: &DerivedKey, cipher: Cipher, padding: Option<Padding>, context: &Context) -> Result<String> {
+    let header = Header {
+        binding: if context.image.is_some() { Binding::Image } else { Binding::Slot },
+        cipher,
+        key_id: key.id().map(str::to_owned),
+        padded: padding.is_some(),
+    };
+    let header_text = header.to_text();
+    let aad = context.associated_data(&header, &header_text)?;
+
+    // 生成随机nonce（XChaCha20-Poly1305使用24字节nonce，其余为12字节）
+    let mut nonce_bytes = vec![0u8; cipher.nonce_len()];
     OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    
-    // 创建加密器
-    let cipher = ChaCha20Poly1305::new_from_slice(key.as_bytes())?;
     
-    // 加密
-    let ciphertext = cipher.encrypt(nonce, plaintext.as_bytes())?;
+    // 填充并加密
+    let plaintext = match padding {
+        Some(padding) => padding.pad(plaintext.as_bytes())?,
+        None => plaintext.as_bytes().to_vec(),
+    };
+    let ciphertext = cipher.encrypt(&key.key, &nonce_bytes, Payload { msg: &plaintext, aad: &aad })?;
     
-    // 组合：salt + nonce + ciphertext
-    let salt_b64 = general_purpose::STANDARD.encode(salt.as_str().as_bytes());
+    // 组合：header + salt + nonce + ciphertext，密钥文件的salt为空
+    let salt_b64 = general_purpose::STANDARD.encode(key.salt().unwrap_or_default().as_bytes());
     let nonce_b64 = general_purpose::STANDARD.encode(&nonce_bytes);
     let ciphertext_b64 = general_purpose::STANDARD.encode(&ciphertext);
-    let combined = format!("{}::{}::{}", salt_b64, nonce_b64, ciphertext_b64);
+    let combined = format!("{}::{}::{}::{}", header_text, salt_b64, nonce_b64, ciphertext_b64);
     
     Ok(combined)
 }
 
-pub fn decrypt(encrypted: &str, password: &str) -> Result<String> {
-    let parts: Vec<&str> = encrypted.split("::").collect();
-    if parts.len() != 3 {
-        return Ok(String::from(encrypted));
-    }
-    if password.is_empty() {
-        return Err("Need password to decrypt".into());
-    }
-    
-    let salt_str = String::from_utf8(general_purpose::STANDARD.decode(parts[0])?)?;
-    let nonce_bytes = general_purpose::STANDARD.decode(parts[1])?;
-    let ciphertext = general_purpose::STANDARD.decode(parts[2])?;
-    
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    
-    // 使用Argon2重新派生密钥
-    let argon2 = Argon2::default();
-    let salt = SaltString::from_b64(&salt_str)?;
-    let password_hash = argon2.hash_password(password.as_bytes(), &salt)?;
-    let key = password_hash.hash.unwrap();
-    
+fn decrypt_envelope(envelope: &Envelope, key: &DerivedKey, context: &Context) -> Result<String> {
+    let aad = match &envelope.header {
+        Some(header) => context.associated_data(header, &envelope.header_text)?,
+        None => Vec::new(),
+    };
+
     // 解密
-    let cipher = ChaCha20Poly1305::new_from_slice(key.as_bytes())?;
-    let plaintext_bytes = cipher.decrypt(nonce, ciphertext.as_slice())?;
+    let plaintext_bytes = envelope.cipher().decrypt(&key.key, &envelope.nonce, Payload { msg: &envelope.ciphertext, aad: &aad })
+        .map_err(|_| "Decryption failed, wrong password or the payload was moved from another image or chunk")?;
     
-    Ok(String::from_utf8(plaintext_bytes)?)
+    // 去掉填充
+    match &envelope.header {
+        Some(header) if header.padded => Ok(String::from_utf8(Padding::unpad(&plaintext_bytes)?.to_vec())?),
+        _ => Ok(String::from_utf8(plaintext_bytes)?),
+    }
+}
+
+type CachedKey = Arc<OnceLock<std::result::Result<Arc<DerivedKey>, String>>>;
+
+/// 按salt缓存派生的密钥，批量解密时相同salt只派生一次；密钥文件的密钥按ID查找
+pub struct KeyCache {
+    password: String,
+    keys: Mutex<HashMap<String, CachedKey>>,
+    key_files: HashMap<String, Arc<DerivedKey>>,
+}
+
+impl KeyCache {
+    pub fn new(password: &str) -> KeyCache {
+        KeyCache {
+            password: password.to_owned(),
+            keys: Mutex::new(HashMap::new()),
+            key_files: HashMap::new(),
+        }
+    }
+
+    /// 加入密钥文件中的密钥，载荷的密文头记录了密钥ID时使用
+    pub fn with_key_files(mut self, keys: Vec<DerivedKey>) -> KeyCache {
+        for key in keys {
+            if let Some(id) = key.id() {
+                self.key_files.insert(id.to_owned(), Arc::new(key));
+            }
+        }
+        self
+    }
+
+    /// 用密码和`salt`派生的密钥，同一个salt只派生一次
+    pub fn key(&self, salt: &str) -> Result<Arc<DerivedKey>> {
+        let cached = self.keys.lock().unwrap().entry(salt.to_owned()).or_default().clone();
+        // 不同salt可以并行派生，相同salt等待第一次派生的结果
+        let key = cached.get_or_init(|| {
+            SaltString::from_b64(salt)
+                .map_err(|e| e.to_string())
+                .and_then(|salt| DerivedKey::derive(&self.password, salt).map_err(|e| e.to_string()))
+                .map(Arc::new)
+        });
+        Ok(key.clone()?)
+    }
+
+    /// 密钥文件或密钥环中ID为`id`的密钥
+    pub fn key_file(&self, id: &str) -> Option<Arc<DerivedKey>> {
+        self.key_files.get(id).cloned()
+    }
+
+    pub fn decrypt(&self, encrypted: &str, context: &Context) -> Result<String> {
+        let Some(envelope) = parse(encrypted) else {
+            return Ok(String::from(encrypted));
+        };
+        let envelope = envelope?;
+        if let Some(id) = envelope.key_id() {
+            let key = self.key_files.get(id).ok_or_else(|| format!("Payload was encrypted with key {}, pass its key file with --key-file or add it to the keyring", id))?;
+            return decrypt_envelope(&envelope, key, context);
+        }
+        if self.password.is_empty() {
+            return Err("Need password to decrypt".into());
+        }
+        let key = self.key(&envelope.salt)?;
+        decrypt_envelope(&envelope, &key, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CIPHERS: [Cipher; 3] = [Cipher::ChaCha20Poly1305, Cipher::XChaCha20Poly1305, Cipher::Aes256GcmSiv];
+
+    fn key() -> DerivedKey {
+        DerivedKey::from_key(vec![7; 32])
+    }
+
+    fn keys() -> KeyCache {
+        KeyCache::new("").with_key_files(vec![key()])
+    }
+
+    fn context(chunk_type: &str, image: Option<[u8; 32]>) -> Context<'_> {
+        Context { format: "png", chunk_type, image }
+    }
+
+    #[test]
+    fn every_cipher_round_trips() {
+        for cipher in CIPHERS {
+            let encrypted = encrypt_with("secret", &key(), cipher, None, &context("ruSt", None)).unwrap();
+            assert_eq!(super::cipher(&encrypted), Some(cipher));
+            assert_eq!(inspect(encrypted.as_bytes()).cipher, Some(cipher.name()));
+            assert_eq!(keys().decrypt(&encrypted, &context("ruSt", None)).unwrap(), "secret");
+        }
+    }
+
+    #[test]
+    fn password_round_trips() {
+        let encrypted = encrypt_with("secret", &DerivedKey::generate("123456").unwrap(), Cipher::default(), None, &context("ruSt", None)).unwrap();
+        assert!(salt(&encrypted).is_some());
+        assert_eq!(KeyCache::new("123456").decrypt(&encrypted, &context("ruSt", None)).unwrap(), "secret");
+        assert!(KeyCache::new("654321").decrypt(&encrypted, &context("ruSt", None)).is_err());
+    }
+
+    #[test]
+    fn altered_header_fails() {
+        let encrypted = encrypt_with("secret", &key(), Cipher::ChaCha20Poly1305, None, &context("ruSt", None)).unwrap();
+        let swapped = encrypted.replace("cipher=chacha20poly1305", "cipher=aes256gcmsiv");
+        assert!(keys().decrypt(&swapped, &context("ruSt", None)).is_err());
+        let padded = encrypted.replacen("::", ";pad=1::", 1);
+        assert!(keys().decrypt(&padded, &context("ruSt", None)).is_err());
+    }
+
+    #[test]
+    fn altered_slot_fails() {
+        let encrypted = encrypt_with("secret", &key(), Cipher::default(), None, &context("ruSt", None)).unwrap();
+        assert!(keys().decrypt(&encrypted, &context("teXt", None)).is_err());
+        let other_format = Context { format: "gif", chunk_type: "ruSt", image: None };
+        assert!(keys().decrypt(&encrypted, &other_format).is_err());
+    }
+
+    #[test]
+    fn altered_image_digest_fails() {
+        let encrypted = encrypt_with("secret", &key(), Cipher::default(), None, &context("ruSt", Some([1; 32]))).unwrap();
+        assert!(binds_image(&encrypted));
+        assert_eq!(keys().decrypt(&encrypted, &context("ruSt", Some([1; 32]))).unwrap(), "secret");
+        assert!(keys().decrypt(&encrypted, &context("ruSt", Some([2; 32]))).is_err());
+        assert!(keys().decrypt(&encrypted, &context("ruSt", None)).is_err());
+    }
+
+    #[test]
+    fn bucket_padding_rounds_up_to_a_power_of_two() {
+        assert_eq!(Padding::Bucket.pad(b"hi").unwrap().len(), 64);
+        assert_eq!(Padding::Bucket.pad(&[1; 60]).unwrap().len(), 64);
+        assert_eq!(Padding::Bucket.pad(&[1; 61]).unwrap().len(), 128);
+        assert_eq!(Padding::unpad(&Padding::Bucket.pad(b"hi").unwrap()).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn random_padding_stays_in_range() {
+        for message in [&b""[..], b"hi", &[1; 1000]] {
+            let framed = LENGTH_PREFIX + message.len();
+            let padded = Padding::Random.pad(message).unwrap();
+            assert!((framed..=framed + framed.max(256)).contains(&padded.len()));
+            assert_eq!(Padding::unpad(&padded).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn fixed_padding_rejects_oversize_messages() {
+        assert_eq!(Padding::Fixed(100).pad(b"hi").unwrap().len(), 100);
+        assert_eq!(Padding::Fixed(6).pad(b"hi").unwrap().len(), 6);
+        assert!(Padding::Fixed(5).pad(b"hi").is_err());
+        assert!(Padding::Fixed(0).pad(b"").is_err());
+        assert_eq!("0".parse::<Padding>(), Ok(Padding::Fixed(0)));
+        assert_eq!(MAX_FIXED_PADDING.to_string().parse::<Padding>(), Ok(Padding::Fixed(MAX_FIXED_PADDING)));
+        assert!((MAX_FIXED_PADDING + 1).to_string().parse::<Padding>().is_err());
+        assert!(usize::MAX.to_string().parse::<Padding>().is_err());
+    }
+
+    #[test]
+    fn invalid_length_prefix_fails() {
+        assert!(Padding::unpad(&[0, 0, 0]).is_err());
+        assert!(Padding::unpad(&[0, 0, 0, 9, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn padded_payloads_hide_the_length() {
+        let short = encrypt_with("a", &key(), Cipher::default(), Some(Padding::Bucket), &context("ruSt", None)).unwrap();
+        let long = encrypt_with(&"a".repeat(50), &key(), Cipher::default(), Some(Padding::Bucket), &context("ruSt", None)).unwrap();
+        assert_eq!(short.len(), long.len());
+        assert!(inspect(short.as_bytes()).padded);
+        assert_eq!(padding(&short), Some(Padding::Fixed(64)));
+        assert_eq!(keys().decrypt(&long, &context("ruSt", None)).unwrap(), "a".repeat(50));
+    }
 }
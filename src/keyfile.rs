@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::Path;
+
+use argon2::password_hash::rand_core::RngCore;
+use base64::{Engine as _, engine::general_purpose};
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Serialize};
+
+use crate::key::{self, DerivedKey};
+use crate::Result;
+
+/// 密钥文件格式的版本
+const VERSION: u32 = 1;
+
+/// 随机生成的256位对称密钥，直接用于加密，不经过KDF
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyFile {
+    keyfile: u32,
+    /// 由密钥计算出的ID，记录在载荷的密文头中
+    id: String,
+    /// 密钥环中的名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    key: String,
+}
+
+impl KeyFile {
+    pub fn generate() -> KeyFile {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        KeyFile { keyfile: VERSION, id: key::key_id(&key), name: None, key: general_purpose::STANDARD.encode(key) }
+    }
+
+    pub fn parse(text: &str) -> Result<KeyFile> {
+        let file: KeyFile = serde_json::from_str(text).map_err(|_| "Not a pngkey key file")?;
+        if file.keyfile != VERSION {
+            return Err(format!("Unsupported key file version {}", file.keyfile).into());
+        }
+        Ok(file)
+    }
+
+    pub fn to_text(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_owned());
+    }
+
+    /// 解码密钥并确认与ID一致
+    pub fn key(&self) -> Result<DerivedKey> {
+        let key = general_purpose::STANDARD.decode(&self.key)?;
+        if key.len() != 32 || key::key_id(&key) != self.id {
+            return Err(format!("Key file {} is corrupted", self.id).into());
+        }
+        Ok(DerivedKey::from_key(key))
+    }
+}
+
+/// 读取并解析一个密钥文件
+pub fn open(path: &Path) -> Result<KeyFile> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(KeyFile::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?)
+}
+
+/// 读取一个密钥文件
+pub fn read(path: &Path) -> Result<DerivedKey> {
+    open(path)?.key()
+}
+
+/// 读取一个密钥文件，或目录中的所有密钥文件；目录中不是密钥文件的文件被忽略
+pub fn load(path: &Path) -> Result<Vec<DerivedKey>> {
+    if !path.is_dir() {
+        return Ok(vec![read(path)?]);
+    }
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if let Some(file) = fs::read_to_string(&path).ok().and_then(|text| KeyFile::parse(&text).ok()) {
+            keys.push(file.key()?);
+        }
+    }
+    Ok(keys)
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::key::DerivedKey;
+use crate::keyfile::{self, KeyFile};
+use crate::write;
+use crate::Result;
+
+/// 密钥环目录：$XDG_CONFIG_HOME/pngkey/keys，未设置时为~/.config/pngkey/keys
+pub fn dir() -> Result<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(|home| Path::new(&home).join(".config")))
+        .ok_or("Cannot find the home directory for the keyring")?;
+    Ok(config.join("pngkey").join("keys"))
+}
+
+/// 名称只能包含字母、数字、`-`、`_`与`.`，不能以`.`开头，直接用作文件名
+fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(format!("Invalid key name {}, use letters, digits, '-', '_' and '.'", name).into());
+    }
+    Ok(())
+}
+
+fn path(name: &str) -> Result<PathBuf> {
+    check_name(name)?;
+    Ok(dir()?.join(format!("{}.json", name)))
+}
+
+/// 按名称排列的所有密钥，目录不存在时为空
+pub fn list() -> Result<Vec<KeyFile>> {
+    list_in(&dir()?)
+}
+
+/// 无法读取或解析的文件在标准错误中警告后跳过，不影响其他密钥
+fn list_in(dir: &Path) -> Result<Vec<KeyFile>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            match keyfile::open(&path) {
+                Ok(file) => files.push(file),
+                Err(e) => eprintln!("Warning: skipping keyring file {}", e),
+            }
+        }
+    }
+    files.sort_by(|a, b| a.name().cmp(&b.name()));
+    Ok(files)
+}
+
+/// 以`name`保存密钥，名称或密钥已在密钥环中时返回错误
+pub fn add(name: &str, mut file: KeyFile) -> Result<KeyFile> {
+    let path = path(name)?;
+    if let Some(existing) = list()?.into_iter().find(|existing| existing.id() == file.id()) {
+        return Err(format!("Key {} is already in the keyring as {}", file.id(), existing.name().unwrap_or_default()).into());
+    }
+    if path.exists() {
+        return Err(format!("Key {} already exists", name).into());
+    }
+    file.set_name(name);
+    fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+    write::write_private(&path, format!("{}\n", file.to_text()?).as_bytes())?;
+    Ok(file)
+}
+
+pub fn get(name: &str) -> Result<KeyFile> {
+    let path = path(name)?;
+    if !path.exists() {
+        return Err(format!("Key {} not found in keyring", name).into());
+    }
+    keyfile::open(&path)
+}
+
+pub fn remove(name: &str) -> Result<KeyFile> {
+    let file = get(name)?;
+    fs::remove_file(path(name)?)?;
+    Ok(file)
+}
+
+/// 解码时按密钥ID自动选用的密钥；密钥环读取失败时忽略，只使用显式指定的密码或密钥文件
+pub fn keys() -> Vec<DerivedKey> {
+    keys_from(list().unwrap_or_default())
+}
+
+/// 密钥损坏的文件警告后跳过
+fn keys_from(files: Vec<KeyFile>) -> Vec<DerivedKey> {
+    files
+        .iter()
+        .filter_map(|file| file.key().map_err(|e| eprintln!("Warning: skipping keyring key: {}", e)).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempFile;
+
+    #[test]
+    fn corrupt_files_are_skipped() {
+        let valid = KeyFile::generate();
+        let dir = TempFile::new("a.json", valid.to_text().unwrap().as_bytes());
+        fs::write(dir.dir().join("broken.json"), "{").unwrap();
+        let mut corrupted = KeyFile::generate().to_text().unwrap();
+        corrupted = corrupted.replace("\"key\": \"", "\"key\": \"AAAA");
+        fs::write(dir.dir().join("corrupted.json"), corrupted).unwrap();
+
+        let files = list_in(dir.dir()).unwrap();
+        assert_eq!(files.len(), 2);
+        let keys = keys_from(files);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].id(), Some(valid.id()));
+    }
+}
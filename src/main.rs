@@ -1,30 +1,57 @@
+use std::process::ExitCode;
+
 use clap::Parser;
 
 mod args;
+mod batch;
 mod commands;
 mod png;
 mod jpg;
 mod gif;
 mod key;
+mod keyfile;
+mod keyring;
+mod hexdump;
+mod report;
+mod seal;
+mod share;
+mod slot;
+mod spread;
+mod source;
+mod strip;
+mod stream;
+mod vault;
+mod verify;
+mod write;
 
 #[cfg(feature = "gui")]
 mod gui;
 
+#[cfg(test)]
+mod fixtures;
+
 
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = std::result::Result<T, Error>;
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = args::Cli::parse();
     // println!("{:?}", args);
     let res = match args.subcommand {
-        Some(args::PngKeyArgs::Encode(encode_args)) => commands::encode(encode_args),
-        Some(args::PngKeyArgs::Decode(decode_args)) => {
-            let _ = commands::decode(decode_args)?;
-            Ok(())
-        },
-        Some(args::PngKeyArgs::Remove(remove_args)) => commands::remove(remove_args),
-        Some(args::PngKeyArgs::Print(print_args)) => commands::print(print_args),
+        Some(args::PngKeyArgs::Encode(encode_args)) => commands::encode(encode_args, args.format),
+        Some(args::PngKeyArgs::Decode(decode_args)) => commands::decode(decode_args, args.format).map(|_| ()),
+        Some(args::PngKeyArgs::Remove(remove_args)) => commands::remove(remove_args, args.format),
+        Some(args::PngKeyArgs::Print(print_args)) => commands::print(print_args, args.format),
+        Some(args::PngKeyArgs::Scan(scan_args)) => commands::scan(scan_args, args.format),
+        Some(args::PngKeyArgs::Strip(strip_args)) => commands::strip(strip_args, args.format),
+        Some(args::PngKeyArgs::Vault(vault_args)) => commands::vault(vault_args, args.format),
+        Some(args::PngKeyArgs::Rekey(rekey_args)) => commands::rekey(rekey_args, args.format),
+        Some(args::PngKeyArgs::Seal(seal_args)) => commands::seal(seal_args, args.format),
+        Some(args::PngKeyArgs::CheckSeal(check_args)) => commands::check_seal(check_args, args.format),
+        Some(args::PngKeyArgs::Keygen(keygen_args)) => commands::keygen(keygen_args, args.format),
+        Some(args::PngKeyArgs::Key(key_args)) => commands::key(key_args, args.format),
+        Some(args::PngKeyArgs::Split(split_args)) => commands::split(split_args, args.format),
+        Some(args::PngKeyArgs::Combine(combine_args)) => commands::combine(combine_args, args.format),
         None => {
             #[cfg(feature = "gui")]
             {
@@ -34,14 +61,16 @@ fn main() -> Result<()> {
 
             #[cfg(not(feature = "gui"))]
             {
-                eprintln!("Error: No subcommand provided. use --help for more information.");
-                Ok(())
+                Err("No subcommand provided. use --help for more information.".into())
             }
         },
     };
-    if let Err(e) = res {
-        eprintln!("Error: {}", e);
+    // 任何命令出错时以非零状态退出，脚本可以据此判断
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
     }
-
-    Ok(())
 }
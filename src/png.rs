@@ -2,10 +2,14 @@ use std::convert::TryFrom;
 
 mod chunk;
 mod chunk_type;
+mod describe;
+mod stream;
+mod slot;
+mod strip;
 pub mod command;
 
 use crate::{Error, Result};
-use chunk::Chunk;
+use chunk::{Chunk, ChunkRef};
 
 /// PNG结构
 #[derive(Debug)]
@@ -18,46 +22,49 @@ impl Png {
     /// 固定开头
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71 ,13, 10, 26, 10];
 
-    /// 添加chunk到png
+    /// 添加chunk到png，以IEND结尾时插入到IEND之前
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        match self.chunks.last() {
+            Some(last) if last.chunk_type().to_string() == "IEND" => {
+                self.chunks.insert(self.chunks.len() - 1, chunk);
+            }
+            _ => self.chunks.push(chunk),
+        }
     }
 
     /// 搜索特定chunk_type的Chunk并移除
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         if let Some(index) = self.chunk_by_type(chunk_type) {
-            return Ok(self.chunks.remove(index));
+            Ok(self.chunks.remove(index))
         } else {
-            return Err(format!("PNG does not contain chunk type {}", chunk_type).into());
+            Err(format!("PNG does not contain chunk type {}", chunk_type).into())
         }
     }
 
+    /// 移除特定位置的chunk
+    pub fn remove_chunk_at(&mut self, index: usize) -> Chunk {
+        self.chunks.remove(index)
+    }
+
     /// 修改特定位置的chunk
     pub fn modify_chunk(&mut self, index: usize, data: Vec<u8>) {
         self.chunks[index].set_data(data);
     }
 
-    pub fn chunks(&self) -> &[Chunk] {
-        &self.chunks
-    }
-
-    /// 找到第一个符合条件的Chunk
+    /// 找到第一个符合条件的Chunk，命名槽位按名称查找
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<usize> {
-        let index = self
+        self
             .chunks.iter()
-            .position(|chunk| format!("{}", chunk.chunk_type()) == chunk_type);
-        index
+            .position(|chunk| chunk.borrowed().slot_type() == chunk_type)
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+        let size = self.header.len() + self.chunks.iter().map(|chunk| chunk.data().len() + 12).sum::<usize>();
+        let mut bytes = Vec::with_capacity(size);
         bytes.extend(&self.header);
-        bytes.extend(
-            self.chunks
-               .iter()
-               .flat_map(|chunk| chunk.as_bytes())
-               .collect::<Vec<u8>>(),
-        );
+        for chunk in &self.chunks {
+            chunk.write_to(&mut bytes);
+        }
         bytes
     }
 }
@@ -74,13 +81,53 @@ impl TryFrom<&[u8]> for Png {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Png> {
+        Ok(PngRef::try_from(bytes)?.to_png())
+    }
+}
+
+/// 借用源数据的PNG结构，decode、print、scan等只读操作不复制块数据
+#[derive(Debug)]
+pub struct PngRef<'a> {
+    bytes: &'a [u8],
+    chunks: Vec<ChunkRef<'a>>,
+}
+
+impl<'a> PngRef<'a> {
+    pub fn chunks(&self) -> &[ChunkRef<'a>] {
+        &self.chunks
+    }
+
+    /// 块在源文件中的原始字节
+    pub fn chunk_bytes(&self, chunk: &ChunkRef) -> &'a [u8] {
+        let offset = chunk.offset().unwrap_or_default();
+        &self.bytes[offset..offset + chunk.size()]
+    }
+
+    /// 复制出可修改的PNG
+    pub fn to_png(&self) -> Png {
+        Png {
+            header: self.bytes[..8].try_into().unwrap(),
+            chunks: self.chunks.iter().map(ChunkRef::to_chunk).collect(),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<PngRef<'a>> {
         // if !is_png(bytes) {
         //     return Err("Not a PNG file".into());
         // }
-        let header: [u8; 8] = bytes[..8].try_into().unwrap();
+        if bytes.len() < 8 {
+            return Err("PNG file is too short".into());
+        }
         let mut chunks = Vec::new();
         let mut index = 8;
         while index < bytes.len() {
+            if index + 12 > bytes.len() {
+                return Err("PNG chunk is too long".into());
+            }
             let length_bytes: [u8; 4] = bytes[index..index+4].try_into().unwrap();
             let length = u32::from_be_bytes(length_bytes) as usize;
             let chunk_end = index + 4 + 4 + length + 4;
@@ -88,10 +135,11 @@ impl TryFrom<&[u8]> for Png {
                 return Err("PNG chunk is too long".into());
             }
             let chunk_bytes = &bytes[index..chunk_end];
-            let chunk = Chunk::try_from(chunk_bytes)?;
+            let mut chunk = ChunkRef::try_from(chunk_bytes)?;
+            chunk.set_offset(index);
             chunks.push(chunk);
             index = chunk_end;
         }
-        Ok(Png { header, chunks })
+        Ok(PngRef { bytes, chunks })
     }
 }
@@ -1,10 +1,10 @@
 use std::convert::TryFrom;
 use std::fmt;
 
-use crc;
-
 use crate::{Error, Result};
 use super::chunk_type::ChunkType;
+use super::describe::describe;
+use super::slot;
 
 /// PNG块
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
@@ -14,15 +14,18 @@ pub struct Chunk {
     chunk_type: ChunkType,
     data: Vec<u8>,
     crc: u32,
+    /// 在源文件中的偏移，新建的块为None
+    offset: Option<usize>,
 }
 
+/// PNG块使用的CRC-32
+pub static PNG_CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
 fn calculate_crc(chunk_type: &[u8; 4], data: &[u8]) -> u32 {
-    let crc_content = chunk_type
-        .iter()
-        .chain(data)
-        .copied()
-        .collect::<Vec<u8>>();
-    crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(&crc_content)
+    let mut digest = PNG_CRC.digest();
+    digest.update(chunk_type);
+    digest.update(data);
+    digest.finalize()
 }
 
 impl Chunk {
@@ -34,13 +37,10 @@ impl Chunk {
             chunk_type,
             data,
             crc,
+            offset: None,
         }
     }
 
-    pub fn length(&self) -> u32 {
-        self.length
-    }
-
     pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
     }
@@ -55,17 +55,28 @@ impl Chunk {
         self.crc = calculate_crc(&self.chunk_type.bytes(), &self.data);
     }
 
-    pub fn crc(&self) -> u32 {
-        self.crc
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.data.len() + 12);
+        self.write_to(&mut bytes);
+        bytes
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    /// 追加到已有的缓冲区，避免为每个块单独分配
+    pub fn write_to(&self, bytes: &mut Vec<u8>) {
         bytes.extend(&self.length.to_be_bytes());
         bytes.extend(&self.chunk_type.bytes());
         bytes.extend(&self.data);
         bytes.extend(&self.crc.to_be_bytes());
-        bytes
+    }
+
+    /// 借用为只读视图
+    pub fn borrowed(&self) -> ChunkRef<'_> {
+        ChunkRef {
+            chunk_type: self.chunk_type.clone(),
+            data: &self.data,
+            crc: self.crc,
+            offset: self.offset,
+        }
     }
 }
 
@@ -73,34 +84,112 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
+        Ok(ChunkRef::try_from(bytes)?.to_chunk())
+    }
+}
+
+impl fmt::Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.borrowed(), f)
+    }
+}
+
+/// 借用源数据的PNG块，只读操作不复制块数据
+#[derive(Debug, Clone)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+    /// 在源文件中的偏移，新建的块为None
+    offset: Option<usize>,
+}
+
+impl<'a> ChunkRef<'a> {
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: usize) {
+        self.offset = Some(offset);
+    }
+
+    /// 块在文件中占用的全部字节数
+    pub fn size(&self) -> usize {
+        self.data.len() + 12
+    }
+
+    /// 块在命令行中的类型，命名槽位为name:<名称>
+    pub fn slot_type(&self) -> String {
+        slot::slot_type(&self.chunk_type.to_string(), self.data)
+    }
+
+    /// 块中的载荷，命名槽位去掉名称头
+    pub fn payload(&self) -> &'a [u8] {
+        slot::payload(&self.chunk_type.to_string(), self.data)
+    }
+
+    /// 复制出可修改的块
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk {
+            length: self.length(),
+            chunk_type: self.chunk_type.clone(),
+            data: self.data.to_vec(),
+            crc: self.crc,
+            offset: self.offset,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self> {
         if bytes.len() < 12 {
             return Err("Chunk data is too short (<32)".into())
         }
         let chunk_type_bytes: [u8; 4] = bytes[4..8].try_into().unwrap();
         let chunk_type = ChunkType::try_from(chunk_type_bytes)?;
-        let data = bytes[8..bytes.len() - 4].to_vec();
+        let data = &bytes[8..bytes.len() - 4];
         let crc_bytes: [u8; 4] = bytes[bytes.len() - 4..].try_into().unwrap();
         let crc = u32::from_be_bytes(crc_bytes);
-        let chunk = Chunk::new(chunk_type, data);
-        if chunk.crc != crc {
+        if calculate_crc(&chunk_type.bytes(), data) != crc {
             return Err("CRC check failed".into())
         }
-        Ok(chunk)
+        Ok(ChunkRef { chunk_type, data, crc, offset: None })
     }
 }
 
-impl fmt::Display for Chunk {
+impl fmt::Display for ChunkRef<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let data: String;
-        if &self.chunk_type().bytes() == b"IDAT" {
-            data = "<Image Data>".to_owned();
-        } else {
-            data = String::from_utf8_lossy(self.data()).to_string();
-        }
         writeln!(f, "Chunk {{",)?;
         writeln!(f, "  Length: {}", self.length())?;
         writeln!(f, "  Type: {}", self.chunk_type())?;
-        writeln!(f, "  Data: {}", data)?;
+        if let Some(name) = crate::slot::name(&self.slot_type()) {
+            writeln!(f, "  Slot: {}", name)?;
+        }
+        if let Some(fields) = describe(self) {
+            for (name, value) in fields {
+                writeln!(f, "  {}: {}", name, value)?;
+            }
+        } else {
+            writeln!(f, "  Data: {}", String::from_utf8_lossy(self.data()))?;
+        }
         writeln!(f, "  CRC: {}", self.crc())?;
         writeln!(f, "}}",)?;
         Ok(())
@@ -13,6 +13,13 @@ pub struct ChunkType {
     fourth_byte: u8,
 }
 
+/// PNG规范及扩展中已注册的块类型
+static STANDARD_CHUNK_TYPES: [&[u8; 4]; 33] = [
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"acTL", b"cHRM", b"cICP", b"gAMA", b"iCCP", b"mDCV", b"cLLI",
+    b"sBIT", b"sRGB", b"bKGD", b"hIST", b"tRNS", b"eXIf", b"fcTL", b"fdAT", b"pHYs", b"sPLT", b"tIME",
+    b"zTXt", b"iTXt", b"tEXt", b"oFFs", b"pCAL", b"sCAL", b"gIFg", b"gIFx", b"gIFt", b"sTER", b"dSIG",
+];
+
 impl ChunkType {
     /// 原始数据
     pub fn bytes(&self) -> [u8; 4] {
@@ -24,10 +31,15 @@ impl ChunkType {
         ]
     }
 
-    // /// 辅助位
-    // pub fn is_critical(&self) -> bool {
-    //     self.first_byte & 0b0010_0000 == 0
-    // }
+    /// 是否为已注册的标准块
+    pub fn is_standard(&self) -> bool {
+        STANDARD_CHUNK_TYPES.contains(&&self.bytes())
+    }
+
+    /// 辅助位
+    pub fn is_critical(&self) -> bool {
+        self.first_byte & 0b0010_0000 == 0
+    }
 
     // /// 私有位
     // pub fn is_public(&self) -> bool {
@@ -1,12 +1,23 @@
-use std::fs;
+use std::io::{BufRead, Read, Seek, Write};
 use std::str::FromStr;
 
 use crate::args::*;
-use super::chunk::Chunk;
+use super::chunk::{Chunk, ChunkRef};
 use super::chunk_type::ChunkType;
-use super::Png;
+use super::strip::category;
+use super::describe::describe;
+use super::{Png, PngRef};
+use super::slot;
+use super::stream;
 use crate::Result;
+use crate::hexdump;
 use crate::key;
+use crate::report::{ChunkReport, SlotReport};
+use crate::seal::Part;
+use crate::verify::Segment;
+use crate::strip::{self, Stripped};
+use crate::stream::Rewrite;
+use crate::write::Append;
 
 
 /// 判断能否使用
@@ -18,89 +29,229 @@ fn is_valid_chunk_type(chunk_type_str: &str) -> bool {
     !VALID_CHUNK_TYPES.contains(&chunk_type_str)
 }
 
-pub fn encode(args: EncodeArgs, bytes: &Vec<u8>) -> Result<()> {
-    // chunk type可用
-    if !is_valid_chunk_type(&args.chunk_type) {
+/// 检查命令行中的块类型，命名槽位使用的块类型保留给--name
+fn check_chunk_type(chunk_type: &str) -> Result<()> {
+    if !is_valid_chunk_type(chunk_type) {
         return Err(format!("Invalid ChunkType, could not in {VALID_CHUNK_TYPES:?}.").into());
     }
-
-    // 密钥与信息
-    let password = args.password.unwrap_or_else(|| "".to_string());
-    let message = args.message;
-    let encrypted_message: String;
-    if !password.is_empty() {
-        encrypted_message = key::encrypt(&message, &password)?;
-    } else {
-        encrypted_message = message;
+    if chunk_type == slot::NAMED_CHUNK_TYPE {
+        return Err(format!("ChunkType {} is reserved for --name.", chunk_type).into());
     }
-    
-    let mut png = Png::try_from(bytes.as_slice())?;
+    Ok(())
+}
+
+pub fn encode(args: &EncodeArgs, bytes: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+    // chunk type可用
+    check_chunk_type(&args.chunk_type)?;
+
+    let mut png = Png::try_from(bytes)?;
+    let (chunk_type, data) = slot::chunk_data(&args.chunk_type, payload);
 
     // 判断chunk_type是否存在
     if let Some(index) = png.chunk_by_type(&args.chunk_type) {
-        png.modify_chunk(index, encrypted_message.as_bytes().to_vec());
+        png.modify_chunk(index, data);
     } else {
         let new_chunk = Chunk::new(
-            ChunkType::from_str(&args.chunk_type)?,
-            encrypted_message.as_bytes().to_vec(),
+            ChunkType::from_str(&chunk_type)?,
+            data,
         );
         png.append_chunk(new_chunk);
     }
 
-    if let Some(output) = args.output {
-        fs::write(output, png.as_bytes())?;
-    } else {
-        fs::write(args.file_path, png.as_bytes())?;
-    }
-
-    Ok(())
+    Ok(png.as_bytes())
 }
 
-pub fn decode(args: DecodeArgs, bytes: &Vec<u8>) -> Result<String> {
+pub fn decode(args: &DecodeArgs, bytes: &[u8]) -> Result<Vec<u8>> {
     // chunk type可用
-    if !is_valid_chunk_type(&args.chunk_type) {
-        return Err(format!("Invalid ChunkType, could not in {VALID_CHUNK_TYPES:?}.").into());
-    }
+    check_chunk_type(&args.chunk_type)?;
 
-    let png = Png::try_from(bytes.as_slice())?;
+    let png = PngRef::try_from(bytes)?;
     let chunk = png
         .chunks()
         .iter()
-        .find(|chunk| chunk.chunk_type().to_string() == args.chunk_type)
+        .find(|chunk| chunk.slot_type() == args.chunk_type)
         .ok_or("Chunk not found")?;
-    let message = std::str::from_utf8(chunk.data()).expect("Invalid UTF-8");
-    let password = args.password.unwrap_or_else(|| "".to_string());
-    let decrypted_message = key::decrypt(&message, &password)?;
-    println!("{}", decrypted_message);
-    Ok(decrypted_message)
+    Ok(chunk.payload().to_vec())
 }
 
-pub fn remove(args: RemoveArgs, bytes: &Vec<u8>) -> Result<()> {
-    let mut png = Png::try_from(bytes.as_slice())?;
+/// 原地追加的计划：没有该类型的块且文件以IEND结尾时，只需写入新块与IEND
+pub fn append<R: Read + Seek>(args: &EncodeArgs, reader: R, payload: &[u8]) -> Result<Option<Append>> {
+    check_chunk_type(&args.chunk_type)?;
+    // 已有命名槽位时无法只凭块头确定名称，交给完整重写
+    let (chunk_type, data) = slot::chunk_data(&args.chunk_type, payload);
+    let Some((offset, iend)) = stream::append_point(reader, &chunk_type)? else {
+        return Ok(None);
+    };
+    let chunk = Chunk::new(ChunkType::from_str(&chunk_type)?, data).as_bytes();
+    Ok(Some(Append { offset, length: chunk.len(), tail: [chunk, iend].concat() }))
+}
+
+/// 流式编码，只有载荷块读入内存
+pub fn encode_stream<R: BufRead, W: Write>(args: &EncodeArgs, reader: R, writer: W, payload: &[u8]) -> Result<Rewrite> {
+    check_chunk_type(&args.chunk_type)?;
+    stream::rewrite(reader, writer, &args.chunk_type, Some(payload))
+}
+
+pub fn remove(args: &RemoveArgs, bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut png = Png::try_from(bytes)?;
     png.remove_chunk(&args.chunk_type)?;
-    fs::write(args.file_path, png.as_bytes())?;
-    Ok(())
+    Ok(png.as_bytes())
+}
+
+/// 流式删除
+pub fn remove_stream<R: BufRead, W: Write>(args: &RemoveArgs, reader: R, writer: W) -> Result<Rewrite> {
+    stream::rewrite(reader, writer, &args.chunk_type, None)
 }
 
-pub fn print(args: PrintArgs, bytes: &Vec<u8>) -> Result<()> {
+pub fn print(args: PrintArgs, bytes: &[u8]) -> Result<()> {
     let mut chunk_type: String = ("").to_string();
     if let Some(_chunk_type) = args.chunk_type {
         chunk_type = _chunk_type;
     }
 
-    let png = Png::try_from(bytes.as_slice())?;
+    let png = PngRef::try_from(bytes)?;
+    let show = |chunk: &ChunkRef| {
+        if args.hex {
+            let offset = chunk.offset().unwrap_or_default();
+            hexdump::print_chunk(&chunk.chunk_type().to_string(), offset, chunk.size(), chunk.data(), offset + 8, args.limit);
+        } else {
+            println!("{}", chunk);
+        }
+    };
 
     if chunk_type.is_empty() {
         for chunk in png.chunks() {
-            println!("{}", chunk);
+            show(chunk);
         }
     } else {
         let chunk = png
             .chunks()
             .iter()
-            .find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+            .find(|chunk| chunk.slot_type() == chunk_type)
             .ok_or("Chunk not found")?;
-        println!("{}", chunk);
+        show(chunk);
     }
     Ok(())
 }
+
+/// 与文本输出相同：按槽位类型匹配，指定块类型时只列出第一个匹配的块
+pub fn report(args: &PrintArgs, bytes: &[u8]) -> Result<Vec<ChunkReport>> {
+    let png = PngRef::try_from(bytes)?;
+
+    let mut reports = Vec::new();
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.slot_type();
+        if args.chunk_type.as_ref().is_some_and(|ct| *ct != chunk_type) {
+            continue;
+        }
+        let payload = (!chunk.chunk_type().is_standard()).then(|| key::inspect(chunk.payload()));
+        let fields = describe(chunk)
+            .unwrap_or_else(|| vec![("Data", String::from_utf8_lossy(chunk.data()).to_string())]);
+        // 命名槽位的类型为name:<名称>，名称字段为实际的块类型
+        let name = chunk.chunk_type().to_string();
+        reports.push(ChunkReport {
+            index,
+            offset: chunk.offset().unwrap_or_default(),
+            length: chunk.size(),
+            data_length: chunk.data().len(),
+            name: (name != chunk_type).then_some(name),
+            chunk_type,
+            fields,
+            payload,
+        });
+        if args.chunk_type.is_some() {
+            break;
+        }
+    }
+    if args.chunk_type.is_some() && reports.is_empty() {
+        return Err("Chunk not found".into());
+    }
+    Ok(reports)
+}
+
+/// 载荷只写入非标准的辅助块，关键块即使类型未知也不是槽位
+pub fn scan(bytes: &[u8]) -> Result<Vec<SlotReport>> {
+    let png = PngRef::try_from(bytes)?;
+    let slots = png
+        .chunks()
+        .iter()
+        .filter(|chunk| !chunk.chunk_type().is_standard() && !chunk.chunk_type().is_critical())
+        .map(|chunk| SlotReport {
+            offset: chunk.offset().unwrap_or_default(),
+            chunk_type: chunk.slot_type(),
+            payload: key::inspect(chunk.payload()),
+        })
+        .collect();
+    Ok(slots)
+}
+
+/// 所有块的原始字节，用于写入前的校验
+pub fn segments(bytes: &[u8]) -> Result<Vec<Segment>> {
+    let png = PngRef::try_from(bytes)?;
+    let segments = png
+        .chunks()
+        .iter()
+        .map(|chunk| Segment::new(
+            chunk.offset().unwrap_or_default(),
+            chunk.slot_type(),
+            png.chunk_bytes(chunk),
+        ))
+        .collect();
+    Ok(segments)
+}
+
+/// 流式读取所有块的校验和与载荷，用于写入后的校验
+pub fn segments_stream<R: BufRead>(reader: R, chunk_type: &str) -> Result<(Vec<Segment>, Option<Vec<u8>>)> {
+    stream::segments(reader, chunk_type)
+}
+
+/// 像素数据，即所有IDAT块的数据
+pub fn pixel_data(bytes: &[u8]) -> Result<Vec<&[u8]>> {
+    let png = PngRef::try_from(bytes)?;
+    let parts = png
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+        .map(|chunk| chunk.data())
+        .collect();
+    Ok(parts)
+}
+
+/// 封印覆盖的关键块：IHDR、PLTE与IDAT
+pub fn critical_parts(bytes: &[u8]) -> Result<Vec<Part>> {
+    let png = PngRef::try_from(bytes)?;
+    let parts = png
+        .chunks()
+        .iter()
+        .filter(|chunk| matches!(chunk.chunk_type().to_string().as_str(), "IHDR" | "PLTE" | "IDAT"))
+        .map(|chunk| Part::new(chunk.chunk_type().to_string(), png.chunk_bytes(chunk)))
+        .collect();
+    Ok(parts)
+}
+
+/// 按策略删除元数据块
+pub fn strip(args: &StripArgs, bytes: &[u8]) -> Result<(Vec<u8>, Vec<Stripped>)> {
+    let view = PngRef::try_from(bytes)?;
+
+    let mut stripped = Vec::new();
+    for (index, chunk) in view.chunks().iter().enumerate() {
+        let Some(category) = category(chunk).filter(|&c| strip::removes(args, c)) else {
+            continue;
+        };
+        let replacement = (category == StripCategory::Exif)
+            .then(|| strip::reduce_exif(args, chunk.data()))
+            .flatten();
+        stripped.push(Stripped { index, category, replacement });
+    }
+    let mut png = view.to_png();
+    for item in stripped.iter().rev() {
+        match &item.replacement {
+            Some(data) => png.modify_chunk(item.index, data.clone()),
+            None => {
+                png.remove_chunk_at(item.index);
+            }
+        }
+    }
+
+    Ok((png.as_bytes(), stripped))
+}
@@ -0,0 +1,148 @@
+use super::chunk::ChunkRef;
+use crate::report::Fields;
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn be_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// Latin-1文本
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn color_type_name(color_type: u8) -> &'static str {
+    match color_type {
+        0 => "Grayscale",
+        2 => "RGB",
+        3 => "Indexed",
+        4 => "Grayscale + Alpha",
+        6 => "RGBA",
+        _ => "Unknown",
+    }
+}
+
+/// 解析出块中有意义的字段，数据不足或未知的块返回None
+pub fn describe(chunk: &ChunkRef) -> Option<Fields> {
+    let data = chunk.data();
+    let fields: Fields = match &chunk.chunk_type().bytes() {
+        b"IHDR" if data.len() == 13 => vec![
+            ("Width", be_u32(&data[0..4]).to_string()),
+            ("Height", be_u32(&data[4..8]).to_string()),
+            ("Bit depth", data[8].to_string()),
+            ("Color type", format!("{} ({})", data[9], color_type_name(data[9]))),
+            ("Compression", data[10].to_string()),
+            ("Filter", data[11].to_string()),
+            ("Interlace", if data[12] == 1 { "Adam7".to_owned() } else { "None".to_owned() }),
+        ],
+        b"PLTE" => vec![("Entries", (data.len() / 3).to_string())],
+        b"IDAT" => vec![("Data", format!("<Image Data, {} bytes>", data.len()))],
+        b"IEND" => vec![],
+        b"tEXt" => {
+            let split = data.iter().position(|&b| b == 0)?;
+            vec![
+                ("Keyword", latin1(&data[..split])),
+                ("Text", latin1(&data[split + 1..])),
+            ]
+        }
+        b"zTXt" => {
+            let split = data.iter().position(|&b| b == 0)?;
+            vec![
+                ("Keyword", latin1(&data[..split])),
+                ("Compressed text", format!("<{} bytes>", data.len().saturating_sub(split + 2))),
+            ]
+        }
+        b"iTXt" => {
+            let mut parts = data.splitn(2, |&b| b == 0);
+            let keyword = latin1(parts.next()?);
+            let rest = parts.next()?;
+            if rest.len() < 2 {
+                return None;
+            }
+            let compressed = rest[0] == 1;
+            let mut parts = rest[2..].splitn(3, |&b| b == 0);
+            let language = String::from_utf8_lossy(parts.next()?).to_string();
+            let translated = String::from_utf8_lossy(parts.next()?).to_string();
+            let text = parts.next()?;
+            let text = if compressed {
+                format!("<Compressed, {} bytes>", text.len())
+            } else {
+                String::from_utf8_lossy(text).to_string()
+            };
+            vec![
+                ("Keyword", keyword),
+                ("Language", language),
+                ("Translated keyword", translated),
+                ("Text", text),
+            ]
+        }
+        b"pHYs" if data.len() == 9 => vec![
+            ("Pixels per unit X", be_u32(&data[0..4]).to_string()),
+            ("Pixels per unit Y", be_u32(&data[4..8]).to_string()),
+            ("Unit", if data[8] == 1 { "Meter".to_owned() } else { "Unknown".to_owned() }),
+        ],
+        b"gAMA" if data.len() == 4 => vec![
+            ("Gamma", format!("{:.5}", be_u32(data) as f64 / 100000.0)),
+        ],
+        b"cHRM" if data.len() == 32 => {
+            let value = |i: usize| format!("{:.5}", be_u32(&data[i * 4..i * 4 + 4]) as f64 / 100000.0);
+            vec![
+                ("White point", format!("{}, {}", value(0), value(1))),
+                ("Red", format!("{}, {}", value(2), value(3))),
+                ("Green", format!("{}, {}", value(4), value(5))),
+                ("Blue", format!("{}, {}", value(6), value(7))),
+            ]
+        }
+        b"sRGB" if data.len() == 1 => {
+            let intent = match data[0] {
+                0 => "Perceptual",
+                1 => "Relative colorimetric",
+                2 => "Saturation",
+                3 => "Absolute colorimetric",
+                _ => "Unknown",
+            };
+            vec![("Rendering intent", format!("{} ({})", data[0], intent))]
+        }
+        b"iCCP" => {
+            let split = data.iter().position(|&b| b == 0)?;
+            vec![
+                ("Profile name", latin1(&data[..split])),
+                ("Profile", format!("<Compressed, {} bytes>", data.len().saturating_sub(split + 2))),
+            ]
+        }
+        b"tIME" if data.len() == 7 => vec![(
+            "Time",
+            format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                be_u16(&data[0..2]), data[2], data[3], data[4], data[5], data[6]
+            ),
+        )],
+        b"bKGD" => vec![(
+            "Background",
+            data.chunks(2).map(|c| c.iter().map(|b| format!("{:02x}", b)).collect::<String>()).collect::<Vec<_>>().join(" "),
+        )],
+        b"tRNS" => vec![("Data", format!("<Transparency, {} bytes>", data.len()))],
+        b"sBIT" => vec![(
+            "Significant bits",
+            data.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(", "),
+        )],
+        b"eXIf" => vec![("Data", format!("<Exif, {} bytes>", data.len()))],
+        b"acTL" if data.len() == 8 => vec![
+            ("Frames", be_u32(&data[0..4]).to_string()),
+            ("Plays", be_u32(&data[4..8]).to_string()),
+        ],
+        b"fcTL" if data.len() == 26 => vec![
+            ("Sequence", be_u32(&data[0..4]).to_string()),
+            ("Width", be_u32(&data[4..8]).to_string()),
+            ("Height", be_u32(&data[8..12]).to_string()),
+            ("Offset", format!("{}, {}", be_u32(&data[12..16]), be_u32(&data[16..20]))),
+            ("Delay", format!("{}/{}", be_u16(&data[20..22]), be_u16(&data[22..24]))),
+        ],
+        b"fdAT" => vec![("Data", format!("<Frame Data, {} bytes>", data.len()))],
+        _ => return None,
+    };
+    Some(fields)
+}
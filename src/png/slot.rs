@@ -0,0 +1,28 @@
+use crate::slot;
+
+/// 命名槽位使用的私有辅助块，数据以名称头开始
+pub static NAMED_CHUNK_TYPE: &str = "pkNm";
+
+/// 块在命令行中的类型：命名槽位为name:<名称>，其余为块类型本身
+pub fn slot_type(chunk_type: &str, data: &[u8]) -> String {
+    match slot::split(data) {
+        Some((name, _)) if chunk_type == NAMED_CHUNK_TYPE => slot::chunk_type(name),
+        _ => chunk_type.to_owned(),
+    }
+}
+
+/// 块中的载荷，命名槽位去掉名称头
+pub fn payload<'a>(chunk_type: &str, data: &'a [u8]) -> &'a [u8] {
+    match slot::split(data) {
+        Some((_, payload)) if chunk_type == NAMED_CHUNK_TYPE => payload,
+        _ => data,
+    }
+}
+
+/// 槽位对应的块类型与块数据
+pub fn chunk_data(chunk_type: &str, payload: &[u8]) -> (String, Vec<u8>) {
+    match slot::name(chunk_type) {
+        Some(name) => (NAMED_CHUNK_TYPE.to_owned(), slot::with_header(name, payload)),
+        None => (chunk_type.to_owned(), payload.to_vec()),
+    }
+}
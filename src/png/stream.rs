@@ -0,0 +1,234 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::str::FromStr;
+
+use super::Png;
+use super::chunk::{Chunk, PNG_CRC};
+use super::chunk_type::ChunkType;
+use super::slot;
+use crate::Result;
+use crate::stream::{self, BUFFER_SIZE, ChunkCopy, Counter, Rewrite};
+use crate::verify::Segment;
+
+static TOO_LONG: &str = "PNG chunk is too long";
+
+/// 块的长度与类型
+struct Header {
+    offset: usize,
+    length: usize,
+    chunk_type: String,
+    raw: [u8; 8],
+}
+
+/// 逐块读取PNG，块数据直接复制到输出而不读入内存
+struct ChunkReader<R> {
+    reader: R,
+    offset: usize,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> ChunkReader<R> {
+    fn new(mut reader: R) -> Result<ChunkReader<R>> {
+        let mut header = [0u8; 8];
+        if stream::read_full(&mut reader, &mut header)? < 8 || header != Png::STANDARD_HEADER {
+            return Err("Not a PNG file".into());
+        }
+        Ok(ChunkReader { reader, offset: 8, buf: vec![0; BUFFER_SIZE] })
+    }
+
+    /// 下一个块的长度与类型，文件结束时返回None
+    fn header(&mut self) -> Result<Option<Header>> {
+        let mut raw = [0u8; 8];
+        match stream::read_full(&mut self.reader, &mut raw)? {
+            0 => return Ok(None),
+            8 => {}
+            _ => return Err(TOO_LONG.into()),
+        }
+        let length = u32::from_be_bytes(raw[..4].try_into().unwrap()) as usize;
+        let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&raw[4..]).unwrap())?;
+        Ok(Some(Header { offset: self.offset, length, chunk_type: chunk_type.to_string(), raw }))
+    }
+
+    /// 复制块的全部字节到`copy`，同时增量计算并校验CRC
+    fn body(&mut self, header: &Header, copy: &mut ChunkCopy) -> Result<()> {
+        copy.raw(&header.raw)?;
+        let mut crc = PNG_CRC.digest();
+        crc.update(&header.raw[4..]);
+
+        let mut remaining = header.length;
+        while remaining > 0 {
+            let n = remaining.min(self.buf.len());
+            stream::read_exact_or(&mut self.reader, &mut self.buf[..n], TOO_LONG)?;
+            crc.update(&self.buf[..n]);
+            copy.data(&self.buf[..n])?;
+            remaining -= n;
+        }
+
+        let mut stored = [0u8; 4];
+        stream::read_exact_or(&mut self.reader, &mut stored, TOO_LONG)?;
+        copy.raw(&stored)?;
+        if u32::from_be_bytes(stored) != crc.finalize() {
+            return Err("CRC check failed".into());
+        }
+        self.offset += header.length + 12;
+        Ok(())
+    }
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// 读入命名槽位的整个块，块类型换成槽位类型
+    fn named(&mut self, header: &Header) -> Result<(Segment, Vec<u8>)> {
+        let mut bytes = Vec::new();
+        let mut copy = ChunkCopy::new(&mut bytes, true);
+        self.body(header, &mut copy)?;
+        let (segment, data) = copy.finish(header.offset, header.chunk_type.clone());
+        let chunk_type = slot::slot_type(&header.chunk_type, &data.unwrap_or_default());
+        Ok((Segment { chunk_type, ..segment }, bytes))
+    }
+}
+
+impl<R: Read + Seek> ChunkReader<R> {
+    /// 跳过块数据与CRC，不读取也不校验
+    fn skip(&mut self, header: &Header) -> Result<()> {
+        self.reader.seek(SeekFrom::Current(header.length as i64 + 4))?;
+        self.offset += header.length + 12;
+        Ok(())
+    }
+}
+
+fn write_chunk<W: Write>(writer: &mut Counter<W>, chunk_type: &str, payload: &[u8]) -> Result<Segment> {
+    let (raw_type, data) = slot::chunk_data(chunk_type, payload);
+    let bytes = Chunk::new(ChunkType::from_str(&raw_type)?, data).as_bytes();
+    let segment = Segment::new(writer.count, chunk_type.to_owned(), &bytes);
+    writer.write_all(&bytes)?;
+    Ok(segment)
+}
+
+/// 流式改写：替换第一个`chunk_type`块的数据，`payload`为None时删除该块，
+/// 没有该块时把新块插入到结尾的IEND之前。其余块原样复制，不读入内存
+pub fn rewrite<R: Read, W: Write>(reader: R, writer: W, chunk_type: &str, payload: Option<&[u8]>) -> Result<Rewrite> {
+    let mut reader = ChunkReader::new(reader)?;
+    let mut writer = Counter::new(writer);
+    writer.write_all(&Png::STANDARD_HEADER)?;
+
+    let (mut before, mut after) = (Vec::new(), Vec::new());
+    let mut found = false;
+    let mut sink = io::sink();
+    let mut next = reader.header()?;
+    while let Some(header) = next.take() {
+        // 命名槽位需要读入数据才能确定名称
+        let mut named = None;
+        if header.chunk_type == slot::NAMED_CHUNK_TYPE {
+            named = Some(reader.named(&header)?);
+        }
+        let slot_type = named.as_ref().map_or(&header.chunk_type, |(segment, _)| &segment.chunk_type);
+        if !found && slot_type == chunk_type {
+            found = true;
+            match named {
+                Some((segment, _)) => before.push(segment),
+                None => {
+                    let mut copy = ChunkCopy::new(&mut sink, false);
+                    reader.body(&header, &mut copy)?;
+                    before.push(copy.finish(header.offset, header.chunk_type).0);
+                }
+            }
+            if let Some(payload) = payload {
+                after.push(write_chunk(&mut writer, chunk_type, payload)?);
+            }
+            next = reader.header()?;
+            continue;
+        }
+        if let Some(payload) = payload.filter(|_| !found && header.chunk_type == "IEND") {
+            // IEND之后还有块时无法确定是否存在该类型的块，按原顺序复制
+            let mut iend = Vec::new();
+            let mut copy = ChunkCopy::new(&mut iend, false);
+            reader.body(&header, &mut copy)?;
+            let (segment, _) = copy.finish(header.offset, header.chunk_type);
+            next = reader.header()?;
+            if next.is_none() {
+                found = true;
+                after.push(write_chunk(&mut writer, chunk_type, payload)?);
+            }
+            after.push(Segment { offset: writer.count, ..segment.clone() });
+            before.push(segment);
+            writer.write_all(&iend)?;
+            continue;
+        }
+        let offset = writer.count;
+        let segment = match named {
+            Some((segment, bytes)) => {
+                writer.write_all(&bytes)?;
+                segment
+            }
+            None => {
+                let mut copy = ChunkCopy::new(&mut writer, false);
+                reader.body(&header, &mut copy)?;
+                copy.finish(header.offset, header.chunk_type).0
+            }
+        };
+        after.push(Segment { offset, ..segment.clone() });
+        before.push(segment);
+        next = reader.header()?;
+    }
+    if !found {
+        let Some(payload) = payload else {
+            return Err(format!("PNG does not contain chunk type {}", chunk_type).into());
+        };
+        after.push(write_chunk(&mut writer, chunk_type, payload)?);
+    }
+    writer.flush()?;
+
+    Ok(Rewrite { before, after, original_size: reader.offset, size: writer.count })
+}
+
+/// 流式读取所有块的校验和，只读入第一个`chunk_type`块与命名槽位的数据
+pub fn segments<R: Read>(reader: R, chunk_type: &str) -> Result<(Vec<Segment>, Option<Vec<u8>>)> {
+    let mut reader = ChunkReader::new(reader)?;
+    let mut segments = Vec::new();
+    let mut payload = None;
+    let mut sink = io::sink();
+    while let Some(header) = reader.header()? {
+        let named = header.chunk_type == slot::NAMED_CHUNK_TYPE;
+        let keep = named || (payload.is_none() && header.chunk_type == chunk_type);
+        let mut copy = ChunkCopy::new(&mut sink, keep);
+        reader.body(&header, &mut copy)?;
+        let (mut segment, data) = copy.finish(header.offset, header.chunk_type.clone());
+        if let Some(data) = &data {
+            segment.chunk_type = slot::slot_type(&header.chunk_type, data);
+        }
+        if payload.is_none() && segment.chunk_type == chunk_type {
+            payload = data.map(|data| slot::payload(&header.chunk_type, &data).to_vec());
+        }
+        segments.push(segment);
+    }
+    Ok((segments, payload))
+}
+
+/// 原地追加的位置：文件中没有`chunk_type`块且以空的IEND结尾时，返回IEND的偏移与原始字节。
+/// 只读取块头，跳过块数据
+pub fn append_point<R: Read + Seek>(reader: R, chunk_type: &str) -> Result<Option<(u64, Vec<u8>)>> {
+    let mut reader = ChunkReader::new(reader)?;
+    let mut last = None;
+    while let Some(header) = reader.header()? {
+        if header.chunk_type == chunk_type {
+            return Ok(None);
+        }
+        reader.skip(&header)?;
+        last = Some(header);
+    }
+    let Some(iend) = last.filter(|last| last.chunk_type == "IEND" && last.length == 0) else {
+        return Ok(None);
+    };
+    // 跳过块数据时不会发现文件被截断，需要确认IEND完整
+    if reader.reader.seek(SeekFrom::End(0))? != reader.offset as u64 {
+        return Ok(None);
+    }
+    let mut raw = iend.raw.to_vec();
+    let mut crc = [0u8; 4];
+    reader.reader.seek(SeekFrom::Start(iend.offset as u64 + 8))?;
+    stream::read_exact_or(&mut reader.reader, &mut crc, TOO_LONG)?;
+    if u32::from_be_bytes(crc) != PNG_CRC.checksum(&raw[4..]) {
+        return Ok(None);
+    }
+    raw.extend_from_slice(&crc);
+    Ok(Some((iend.offset as u64, raw)))
+}
@@ -0,0 +1,94 @@
+use super::chunk::ChunkRef;
+use crate::args::StripCategory;
+
+/// 块所属的元数据类别，显示图像所必需的块返回None
+pub fn category(chunk: &ChunkRef) -> Option<StripCategory> {
+    let chunk_type = chunk.chunk_type();
+    let category = match chunk_type.to_string().as_str() {
+        "IHDR" | "PLTE" | "IDAT" | "IEND" | "tRNS" | "acTL" | "fcTL" | "fdAT" => return None,
+        "eXIf" => StripCategory::Exif,
+        "iCCP" => StripCategory::Icc,
+        "iTXt" if chunk.data().starts_with(b"XML:com.adobe.xmp\0") => StripCategory::Xmp,
+        "tEXt" | "zTXt" | "iTXt" => StripCategory::Comment,
+        "gAMA" | "cHRM" | "sRGB" | "sBIT" | "cICP" | "mDCV" | "cLLI" => StripCategory::Color,
+        "tIME" => StripCategory::Time,
+        "pHYs" | "oFFs" | "sCAL" => StripCategory::Physical,
+        _ if chunk_type.is_standard() => StripCategory::Other,
+        // 未知的关键块无法安全删除
+        _ if chunk_type.is_critical() => return None,
+        _ => StripCategory::Private,
+    };
+    Some(category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::command;
+    use crate::args::{StripArgs, StripCategory};
+    use crate::fixtures::{self, png_chunk};
+    use crate::strip;
+
+    fn exif() -> Vec<u8> {
+        png_chunk(b"eXIf", &fixtures::exif(6))
+    }
+
+    fn comment() -> Vec<u8> {
+        png_chunk(b"tEXt", b"Comment\0hi")
+    }
+
+    fn payload() -> Vec<u8> {
+        png_chunk(b"ruSt", b"payload")
+    }
+
+    /// 每个类别各一个块
+    fn png() -> Vec<u8> {
+        fixtures::png_with(&[
+            exif(),
+            png_chunk(b"iCCP", b"icc\0\0x"),
+            png_chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x/>"),
+            comment(),
+            png_chunk(b"gAMA", &[0, 0, 0xB1, 0x8F]),
+            png_chunk(b"tIME", &[7, 0xEA, 10, 19, 0, 0, 0]),
+            png_chunk(b"pHYs", &[0, 0, 0, 1, 0, 0, 0, 1, 0]),
+            png_chunk(b"bKGD", &[0, 0]),
+            payload(),
+        ])
+    }
+
+    fn strip(args: &StripArgs) -> (Vec<u8>, Vec<StripCategory>) {
+        let (result, stripped) = command::strip(args, &png()).unwrap();
+        (result, stripped.iter().map(|item| item.category).collect())
+    }
+
+    #[test]
+    fn strips_every_category() {
+        let (result, categories) = strip(&StripArgs::default());
+        assert_eq!(result, fixtures::png());
+        assert_eq!(
+            categories,
+            [
+                StripCategory::Exif,
+                StripCategory::Icc,
+                StripCategory::Xmp,
+                StripCategory::Comment,
+                StripCategory::Color,
+                StripCategory::Time,
+                StripCategory::Physical,
+                StripCategory::Other,
+                StripCategory::Private,
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_and_drops_categories() {
+        let args = StripArgs { keep: vec![StripCategory::Comment, StripCategory::Orientation], ..Default::default() };
+        let reduced = strip::reduce_exif(&args, &fixtures::exif(6)).unwrap();
+        assert_eq!(strip(&args).0, fixtures::png_with(&[png_chunk(b"eXIf", &reduced), comment()]));
+
+        let args = StripArgs { drop: vec![StripCategory::Private], ..Default::default() };
+        let (result, categories) = strip(&args);
+        assert_eq!(categories, [StripCategory::Private]);
+        assert_eq!(result.len(), png().len() - payload().len());
+    }
+}
@@ -0,0 +1,248 @@
+use serde::{Serialize, Serializer, ser::SerializeMap};
+
+use clap::ValueEnum;
+
+use crate::args::{OutputFormat, StripCategory};
+use crate::batch::Input;
+use crate::key::PayloadInfo;
+use crate::Result;
+use crate::seal::Status;
+
+/// 字段名与值，按解析顺序排列
+pub type Fields = Vec<(&'static str, String)>;
+
+/// 以对象形式输出字段，保留顺序
+fn fields_as_map<S: Serializer>(fields: &Fields, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(fields.len()))?;
+    for (name, value) in fields {
+        map.serialize_entry(name, value)?;
+    }
+    map.end()
+}
+
+/// 文件中的块列表
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub file: String,
+    pub format: &'static str,
+    pub size: usize,
+    pub chunks: Vec<ChunkReport>,
+}
+
+/// 单个块
+#[derive(Debug, Serialize)]
+pub struct ChunkReport {
+    pub index: usize,
+    pub offset: usize,
+    /// 块在文件中占用的全部字节数
+    pub length: usize,
+    /// 块数据的字节数
+    pub data_length: usize,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(serialize_with = "fields_as_map")]
+    pub fields: Fields,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<PayloadInfo>,
+}
+
+/// 解码结果
+#[derive(Debug, Serialize)]
+pub struct DecodeReport {
+    pub file: String,
+    pub format: &'static str,
+    pub chunk_type: String,
+    pub payload: PayloadInfo,
+    pub message: String,
+}
+
+/// 可能存放载荷的块
+#[derive(Debug, Serialize)]
+pub struct SlotReport {
+    pub offset: usize,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub payload: PayloadInfo,
+}
+
+/// 单个文件的扫描结果
+#[derive(Debug, Serialize)]
+pub struct ScanReport {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<&'static str>,
+    pub slots: Vec<SlotReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 保险库中的一项
+#[derive(Debug, Serialize)]
+pub struct VaultEntryReport {
+    pub file: String,
+    pub chunk_type: String,
+    pub key: String,
+    pub value: String,
+}
+
+/// 保险库中的所有键
+#[derive(Debug, Serialize)]
+pub struct VaultListReport {
+    pub file: String,
+    pub chunk_type: String,
+    pub keys: Vec<String>,
+}
+
+/// 封印覆盖的一个块
+#[derive(Debug, Serialize)]
+pub struct SealPartReport {
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub status: Status,
+}
+
+/// 封印的检查结果
+#[derive(Debug, Serialize)]
+pub struct SealReport {
+    pub file: String,
+    pub format: &'static str,
+    pub chunk_type: String,
+    /// 所有关键块都与封印时相同
+    pub intact: bool,
+    pub parts: Vec<SealPartReport>,
+}
+
+/// 新生成的密钥文件
+#[derive(Debug, Serialize)]
+pub struct KeygenReport {
+    pub id: String,
+    pub path: String,
+}
+
+/// 由份额恢复的秘密
+#[derive(Debug, Serialize)]
+pub struct CombineReport {
+    pub set: String,
+    pub threshold: u8,
+    /// 参与恢复的份额序号
+    pub shares: Vec<u8>,
+    pub payload: PayloadInfo,
+    pub message: String,
+}
+
+/// 密钥环中的一个密钥
+#[derive(Debug, Serialize)]
+pub struct KeyReport {
+    pub name: String,
+    pub id: String,
+}
+
+/// 写入对块的改动
+#[derive(Debug, Serialize)]
+pub struct ChangeReport {
+    /// added、replaced或deleted
+    pub action: &'static str,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub offset: usize,
+    pub length: usize,
+    /// strip删除的元数据类别
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<StripCategory>,
+}
+
+/// 写入结果
+#[derive(Debug, Serialize)]
+pub struct WriteReport {
+    pub file: String,
+    pub output: String,
+    pub dry_run: bool,
+    /// 原文件大小
+    pub original_size: usize,
+    /// 写入后的文件大小
+    pub size: usize,
+    pub changes: Vec<ChangeReport>,
+}
+
+impl WriteReport {
+    /// 文本格式的改动列表
+    pub fn text(&self) -> String {
+        let mut text = format!("{} -> {} ({} -> {} bytes)\n", self.file, self.output, self.original_size, self.size);
+        for change in &self.changes {
+            let category = change.category
+                .and_then(|category| category.to_possible_value())
+                .map(|value| format!("  ({})", value.get_name()))
+                .unwrap_or_default();
+            text += &format!("  {:<8} {} @ {:#010x}  {} bytes{}\n", change.action, change.chunk_type, change.offset, change.length, category);
+        }
+        text
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.text());
+    }
+}
+
+/// 批量处理中失败的文件
+#[derive(Debug, Serialize)]
+pub struct BatchError {
+    pub file: String,
+    pub error: String,
+}
+
+/// 批量处理汇总
+#[derive(Debug, Serialize)]
+pub struct BatchReport<T: Serialize> {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<T>,
+    pub errors: Vec<BatchError>,
+}
+
+impl<T: Serialize> BatchReport<T> {
+    pub fn new(inputs: &[Input], results: Vec<std::result::Result<T, String>>) -> BatchReport<T> {
+        let mut report = BatchReport {
+            total: inputs.len(),
+            succeeded: 0,
+            failed: 0,
+            results: Vec::new(),
+            errors: Vec::new(),
+        };
+        for (input, result) in inputs.iter().zip(results) {
+            match result {
+                Ok(value) => report.results.push(value),
+                Err(error) => report.errors.push(BatchError { file: input.path.display().to_string(), error }),
+            }
+        }
+        report.succeeded = report.results.len();
+        report.failed = report.errors.len();
+        report
+    }
+
+    /// 文本格式的汇总
+    pub fn print_summary(&self) {
+        for error in &self.errors {
+            eprintln!("{}: Error: {}", error.file, error.error);
+        }
+        println!("{} files, {} succeeded, {} failed", self.total, self.succeeded, self.failed);
+    }
+}
+
+/// 按指定格式生成文本，文本格式由调用方自行处理
+pub fn render<T: Serialize>(value: &T, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(value)? + "\n"),
+        OutputFormat::Yaml => Ok(serde_yaml_ng::to_string(value)?),
+        OutputFormat::Text => Err("Text output has no structured form".into()),
+    }
+}
+
+/// 按指定格式输出
+pub fn emit<T: Serialize>(value: &T, format: OutputFormat) -> Result<()> {
+    print!("{}", render(value, format)?);
+    Ok(())
+}
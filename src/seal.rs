@@ -0,0 +1,112 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::key::DerivedKey;
+use crate::Result;
+
+/// 封印格式的版本
+const VERSION: u32 = 1;
+
+/// MAC的用途标识
+static MAC_DOMAIN: &str = "pngkey-seal";
+
+/// 封印时关键图像数据中的一个块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Part {
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    /// 块原始字节的SHA-256
+    digest: String,
+}
+
+impl Part {
+    pub fn new(chunk_type: String, bytes: &[u8]) -> Part {
+        Part { chunk_type, digest: general_purpose::STANDARD.encode(Sha256::digest(bytes)) }
+    }
+}
+
+/// MAC覆盖的内容
+#[derive(Debug, Serialize, Deserialize)]
+struct Body {
+    seal: u32,
+    format: String,
+    salt: String,
+    parts: Vec<Part>,
+}
+
+/// 关键图像数据的摘要列表及其MAC，以明文JSON保存在载荷块中
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Seal {
+    #[serde(flatten)]
+    body: Body,
+    mac: String,
+}
+
+/// 与封印相比块的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Unchanged,
+    Changed,
+    Added,
+    Missing,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Unchanged => "unchanged",
+            Status::Changed => "changed",
+            Status::Added => "added",
+            Status::Missing => "missing",
+        }
+    }
+}
+
+impl Seal {
+    pub fn new(format: &str, parts: Vec<Part>, key: &DerivedKey) -> Result<Seal> {
+        let body = Body { seal: VERSION, format: format.to_owned(), salt: key.salt().ok_or("A seal needs a password")?.to_owned(), parts };
+        let mac = key.mac(MAC_DOMAIN, serde_json::to_string(&body)?.as_bytes())?;
+        Ok(Seal { body, mac: general_purpose::STANDARD.encode(mac) })
+    }
+
+    pub fn parse(message: &str) -> Result<Seal> {
+        let seal: Seal = serde_json::from_str(message).map_err(|_| "Payload is not a seal")?;
+        if seal.body.seal != VERSION {
+            return Err(format!("Unsupported seal version {}", seal.body.seal).into());
+        }
+        Ok(seal)
+    }
+
+    pub fn to_message(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// 用密码重新派生密钥并校验MAC，确认封印本身没有被伪造或修改
+    pub fn verify(&self, password: &str) -> Result<()> {
+        let key = DerivedKey::from_salt(password, &self.body.salt)?;
+        let tag = general_purpose::STANDARD.decode(&self.mac)?;
+        key.verify_mac(MAC_DOMAIN, serde_json::to_string(&self.body)?.as_bytes(), &tag)
+    }
+
+    pub fn format(&self) -> &str {
+        &self.body.format
+    }
+
+    /// 按顺序比较当前的块与封印时的块
+    pub fn compare(&self, current: &[Part]) -> Vec<(usize, String, Status)> {
+        let sealed = &self.body.parts;
+        (0..sealed.len().max(current.len()))
+            .map(|index| match (sealed.get(index), current.get(index)) {
+                (Some(old), Some(new)) if old.chunk_type == new.chunk_type && old.digest == new.digest => {
+                    (index, new.chunk_type.clone(), Status::Unchanged)
+                }
+                (Some(_), Some(new)) => (index, new.chunk_type.clone(), Status::Changed),
+                (None, Some(new)) => (index, new.chunk_type.clone(), Status::Added),
+                (Some(old), None) => (index, old.chunk_type.clone(), Status::Missing),
+                (None, None) => unreachable!(),
+            })
+            .collect()
+    }
+}
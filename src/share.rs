@@ -0,0 +1,165 @@
+use std::fmt::Write as _;
+
+use argon2::password_hash::rand_core::RngCore;
+use base64::{Engine as _, engine::general_purpose};
+use chacha20poly1305::aead::OsRng;
+use sha2::{Digest, Sha256};
+use sharks::Sharks;
+
+use crate::Result;
+
+/// 份额头的版本标识
+static HEADER_VERSION: &str = "pks1";
+
+/// Shamir秘密共享的一份：pks1;k=2;n=3;set=...::share，
+/// 任意k份可以恢复秘密，少于k份不泄露秘密的任何信息。
+/// 校验和附在秘密之后一起拆分，份额头中不含任何由秘密计算出的值
+#[derive(Debug, Clone)]
+pub struct Share {
+    pub threshold: u8,
+    pub shares: u8,
+    /// 同一次拆分的随机标识，避免混用不同秘密的份额
+    pub set: String,
+    /// 份额的x坐标与各字节的y值
+    data: Vec<u8>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut text, byte| {
+        let _ = write!(text, "{:02x}", byte);
+        text
+    })
+}
+
+/// 附在秘密之后一起拆分的校验和长度
+const CHECKSUM_LEN: usize = 8;
+
+/// SHA-256的前8字节，恢复后用于校验
+fn checksum(secret: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut sum = [0u8; CHECKSUM_LEN];
+    sum.copy_from_slice(&Sha256::digest(secret)[..CHECKSUM_LEN]);
+    sum
+}
+
+/// 每次拆分随机生成的标识
+pub fn set_id() -> String {
+    let mut set = [0u8; 8];
+    OsRng.fill_bytes(&mut set);
+    hex(&set)
+}
+
+impl Share {
+    /// 份额的序号，从1开始
+    pub fn index(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// 不是份额格式时返回None
+    pub fn parse(text: &str) -> Option<Result<Share>> {
+        let (header, data) = text.split_once("::")?;
+        let mut fields = header.split(';');
+        if fields.next() != Some(HEADER_VERSION) {
+            return None;
+        }
+        let share = || -> Result<Share> {
+            let (mut threshold, mut shares, mut set) = (None, None, None);
+            for field in fields {
+                match field.split_once('=') {
+                    Some(("k", value)) => threshold = Some(value.parse()?),
+                    Some(("n", value)) => shares = Some(value.parse()?),
+                    Some(("set", value)) => set = Some(value.to_owned()),
+                    _ => return Err(format!("Unknown share header field {}", field).into()),
+                }
+            }
+            let data = general_purpose::STANDARD.decode(data)?;
+            if data.len() < 2 {
+                return Err("Share is too short".into());
+            }
+            Ok(Share {
+                threshold: threshold.ok_or("Share header has no threshold")?,
+                shares: shares.ok_or("Share header has no share count")?,
+                set: set.ok_or("Share header has no set")?,
+                data,
+            })
+        };
+        Some(share())
+    }
+
+    pub fn to_message(&self) -> String {
+        format!(
+            "{};k={};n={};set={}::{}",
+            HEADER_VERSION, self.threshold, self.shares, self.set, general_purpose::STANDARD.encode(&self.data)
+        )
+    }
+}
+
+/// 把秘密拆分为`shares`份，任意`threshold`份可以恢复
+pub fn split(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>> {
+    if threshold < 2 || threshold > shares {
+        return Err("Threshold must be at least 2 and at most the number of shares".into());
+    }
+    let set = set_id();
+    let mut dealt = secret.to_vec();
+    dealt.extend_from_slice(&checksum(secret));
+    Ok(Sharks(threshold)
+        .dealer(&dealt)
+        .take(shares as usize)
+        .map(|share| Share { threshold, shares, set: set.clone(), data: Vec::from(&share) })
+        .collect())
+}
+
+/// 用同一次拆分中至少`threshold`份不同的份额恢复秘密
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>> {
+    let first = shares.first().ok_or("No shares to combine")?;
+    let parts = shares
+        .iter()
+        .map(|share| sharks::Share::try_from(share.data.as_slice()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let mut secret = Sharks(first.threshold).recover(&parts)?;
+    let Some(split) = secret.len().checked_sub(CHECKSUM_LEN) else {
+        return Err("Shares do not combine to the original secret".into());
+    };
+    let sum = secret.split_off(split);
+    if sum != checksum(&secret) {
+        return Err("Shares do not combine to the original secret".into());
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_threshold_shares_combine() {
+        let shares = split(b"correct horse battery staple", 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert_eq!(combine(&shares[..3]).unwrap(), b"correct horse battery staple");
+        assert_eq!(combine(&shares[2..]).unwrap(), b"correct horse battery staple");
+        assert_eq!(combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap(), b"correct horse battery staple");
+    }
+
+    #[test]
+    fn too_few_shares_fail() {
+        let shares = split(b"secret", 3, 5).unwrap();
+        assert!(combine(&shares[..2]).is_err());
+        assert!(combine(&[]).is_err());
+    }
+
+    #[test]
+    fn shares_from_another_split_fail() {
+        let (a, b) = (split(b"secret one", 2, 3).unwrap(), split(b"secret two", 2, 3).unwrap());
+        assert!(combine(&[a[0].clone(), b[1].clone()]).is_err());
+    }
+
+    #[test]
+    fn message_round_trip_has_no_checksum() {
+        let share = &split(b"secret", 2, 2).unwrap()[0];
+        let message = share.to_message();
+        assert!(!message.contains("sum="));
+        let parsed = Share::parse(&message).unwrap().unwrap();
+        assert_eq!((parsed.threshold, parsed.shares, parsed.set.as_str()), (2, 2, share.set.as_str()));
+        assert_eq!(parsed.data, share.data);
+        assert!(Share::parse("plain message").is_none());
+    }
+}
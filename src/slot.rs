@@ -0,0 +1,40 @@
+use crate::Result;
+
+/// 命名槽位在内部以`name:<名称>`作为块类型，不会与任何格式的块类型冲突
+const PREFIX: &str = "name:";
+
+/// 名称的最大字节数
+const MAX_NAME_LENGTH: usize = 64;
+
+/// 命名槽位的名称，原始块类型返回None
+pub fn name(chunk_type: &str) -> Option<&str> {
+    chunk_type.strip_prefix(PREFIX)
+}
+
+/// 命名槽位的块类型
+pub fn chunk_type(name: &str) -> String {
+    format!("{}{}", PREFIX, name)
+}
+
+/// 由命令行参数得到块类型：指定--name时为命名槽位，否则为原始块类型
+pub fn resolve(chunk_type: &str, name: Option<&str>) -> Result<String> {
+    let Some(name) = name else {
+        return Ok(chunk_type.to_owned());
+    };
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH || name.contains('\0') {
+        return Err(format!("Invalid name, should be 1 to {} bytes without NUL.", MAX_NAME_LENGTH).into());
+    }
+    Ok(self::chunk_type(name))
+}
+
+/// 命名槽位的数据：名称、NUL与载荷
+pub fn with_header(name: &str, payload: &[u8]) -> Vec<u8> {
+    [name.as_bytes(), &[0], payload].concat()
+}
+
+/// 拆分出名称与载荷，没有名称头时返回None
+pub fn split(data: &[u8]) -> Option<(&str, &[u8])> {
+    let end = data.iter().position(|&b| b == 0)?;
+    let name = std::str::from_utf8(&data[..end]).ok().filter(|name| !name.is_empty())?;
+    Some((name, &data[end + 1..]))
+}
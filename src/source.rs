@@ -0,0 +1,52 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::batch;
+use crate::Result;
+
+/// 只读命令的输入数据，块视图直接借用其中的字节
+pub enum Source {
+    /// 内存映射的文件，按需由系统换入，不占用额外内存
+    Mapped(Mmap),
+    /// 标准输入或无法映射的文件
+    Buffer(Vec<u8>),
+}
+
+impl Deref for Source {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Source::Mapped(map) => map,
+            Source::Buffer(bytes) => bytes,
+        }
+    }
+}
+
+/// 打开只读输入：普通文件映射到内存，标准输入及无法映射的文件读入内存
+pub fn open(file_path: &Path) -> Result<Source> {
+    if batch::is_stdio(file_path) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        return Ok(Source::Buffer(bytes));
+    }
+    if !file_path.exists() {
+        return Err("File does not exist".into());
+    }
+    let mut file = File::open(file_path)?;
+    let metadata = file.metadata()?;
+    if metadata.is_file() && metadata.len() > 0 {
+        // 映射期间文件被其他进程截断会导致读取出错，只读命令接受这一点；
+        // 写入命令仍然读入内存，以便在映射存在时替换原文件
+        if let Ok(map) = unsafe { Mmap::map(&file) } {
+            return Ok(Source::Mapped(map));
+        }
+    }
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    Ok(Source::Buffer(bytes))
+}
@@ -0,0 +1,220 @@
+use base64::{Engine as _, engine::general_purpose};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+use crate::share;
+use crate::Result;
+
+/// 分片头的版本标识
+static HEADER_VERSION: &str = "pkf1";
+
+/// GF(2^8)上的Reed-Solomon编码最多支持的分片总数
+const MAX_SHARDS: usize = 256;
+
+/// 分散到多个图像中的载荷的一片：pkf1;k=3;m=1;i=0;len=...;set=...::shard，
+/// 前k片为数据，后m片为Reed-Solomon校验，任意k片可以恢复载荷
+#[derive(Debug, Clone)]
+pub struct Fragment {
+    /// 数据分片数
+    pub data: usize,
+    /// 校验分片数
+    pub parity: usize,
+    /// 分片序号，从0开始
+    pub index: usize,
+    /// 载荷的长度，最后一个数据分片末尾的填充在恢复后去掉
+    len: usize,
+    /// 同一次分散的随机标识，避免混用不同载荷的分片
+    pub set: String,
+    shard: Vec<u8>,
+}
+
+impl Fragment {
+    /// 不是分片格式时返回None
+    pub fn parse(text: &str) -> Option<Result<Fragment>> {
+        let (header, shard) = text.split_once("::")?;
+        let mut fields = header.split(';');
+        if fields.next() != Some(HEADER_VERSION) {
+            return None;
+        }
+        let fragment = || -> Result<Fragment> {
+            let (mut data, mut parity, mut index, mut len, mut set) = (None, None, None, None, None);
+            for field in fields {
+                match field.split_once('=') {
+                    Some(("k", value)) => data = Some(value.parse()?),
+                    Some(("m", value)) => parity = Some(value.parse()?),
+                    Some(("i", value)) => index = Some(value.parse()?),
+                    Some(("len", value)) => len = Some(value.parse()?),
+                    Some(("set", value)) => set = Some(value.to_owned()),
+                    _ => return Err(format!("Unknown fragment header field {}", field).into()),
+                }
+            }
+            let fragment = Fragment {
+                data: data.ok_or("Fragment header has no data shard count")?,
+                parity: parity.ok_or("Fragment header has no parity shard count")?,
+                index: index.ok_or("Fragment header has no index")?,
+                len: len.ok_or("Fragment header has no length")?,
+                set: set.ok_or("Fragment header has no set")?,
+                shard: general_purpose::STANDARD.decode(shard)?,
+            };
+            // 分片头来自图像，分配内存前先检查各个数量
+            let total = fragment.data.checked_add(fragment.parity).filter(|&total| total <= MAX_SHARDS);
+            if fragment.data == 0 || fragment.parity == 0 || total.is_none() {
+                return Err(format!("Fragment shard counts must be at least 1 and at most {} in total", MAX_SHARDS).into());
+            }
+            if total.is_some_and(|total| fragment.index >= total) {
+                return Err("Fragment index is out of range".into());
+            }
+            if fragment.data.checked_mul(fragment.shard.len()).is_none_or(|size| fragment.len > size) {
+                return Err("Fragment length is out of range".into());
+            }
+            Ok(fragment)
+        };
+        Some(fragment())
+    }
+
+    pub fn to_message(&self) -> String {
+        format!(
+            "{};k={};m={};i={};len={};set={}::{}",
+            HEADER_VERSION,
+            self.data,
+            self.parity,
+            self.index,
+            self.len,
+            self.set,
+            general_purpose::STANDARD.encode(&self.shard)
+        )
+    }
+}
+
+/// 把载荷分为`total - parity`个数据分片，再计算`parity`个校验分片
+pub fn split(payload: &[u8], total: usize, parity: usize) -> Result<Vec<Fragment>> {
+    if parity == 0 || parity >= total {
+        return Err("Parity must be at least 1 and less than the number of images".into());
+    }
+    if total > MAX_SHARDS {
+        return Err(format!("Cannot spread over more than {} images", MAX_SHARDS).into());
+    }
+    let data = total - parity;
+    let size = payload.len().div_ceil(data).max(1);
+    let mut shards: Vec<Vec<u8>> = (0..total)
+        .map(|index| {
+            let mut shard = payload.get(index * size..).unwrap_or_default().iter().take(size).copied().collect::<Vec<u8>>();
+            shard.resize(size, 0);
+            shard
+        })
+        .collect();
+    ReedSolomon::new(data, parity)?.encode(&mut shards)?;
+
+    let set = share::set_id();
+    Ok(shards
+        .into_iter()
+        .enumerate()
+        .map(|(index, shard)| Fragment { data, parity, index, len: payload.len(), set: set.clone(), shard })
+        .collect())
+}
+
+/// 用同一次分散中的分片恢复载荷，缺少的分片不超过校验分片数。
+/// 分片多于数据分片数时，恢复后再用多出的分片校验
+pub fn join(fragments: &[Fragment]) -> Result<Vec<u8>> {
+    let first = fragments.first().ok_or("No fragments to join")?;
+    let same = |fragment: &Fragment| {
+        (fragment.data, fragment.parity, fragment.len, &fragment.set, fragment.shard.len())
+            == (first.data, first.parity, first.len, &first.set, first.shard.len())
+    };
+    if !fragments.iter().all(same) {
+        return Err("Fragments do not belong to the same payload".into());
+    }
+    let mut shards: Vec<Option<Vec<u8>>> = vec![None; first.data.saturating_add(first.parity).min(MAX_SHARDS)];
+    for fragment in fragments {
+        let shard = shards.get_mut(fragment.index).ok_or("Fragment index is out of range")?;
+        *shard = Some(fragment.shard.clone());
+    }
+    let present = shards.iter().filter(|shard| shard.is_some()).count();
+    if present < first.data {
+        return Err(format!("Need {} of {} fragments to rebuild the payload, found {}", first.data, shards.len(), present).into());
+    }
+    let rs = ReedSolomon::new(first.data, first.parity)?;
+    rs.reconstruct(&mut shards)?;
+    let shards: Vec<Vec<u8>> = shards.into_iter().flatten().collect();
+    if !rs.verify(&shards)? || first.data.checked_mul(first.shard.len()).is_none_or(|size| first.len > size) {
+        return Err("Fragments do not rebuild the original payload".into());
+    }
+
+    let mut payload: Vec<u8> = shards.into_iter().take(first.data).flatten().collect();
+    payload.truncate(first.len);
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"a payload that does not divide evenly into shards";
+
+    #[test]
+    fn joins_with_up_to_parity_missing() {
+        let fragments = split(PAYLOAD, 5, 2).unwrap();
+        assert_eq!(join(&fragments).unwrap(), PAYLOAD);
+        assert_eq!(join(&fragments[2..]).unwrap(), PAYLOAD);
+        assert_eq!(join(&[fragments[0].clone(), fragments[2].clone(), fragments[4].clone()]).unwrap(), PAYLOAD);
+        assert!(join(&fragments[3..]).is_err());
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let fragments: Vec<Fragment> = split(PAYLOAD, 3, 1)
+            .unwrap()
+            .iter()
+            .map(|fragment| Fragment::parse(&fragment.to_message()).unwrap().unwrap())
+            .collect();
+        assert!(!fragments[0].to_message().contains("sum="));
+        assert_eq!(join(&fragments[1..]).unwrap(), PAYLOAD);
+        assert!(Fragment::parse("plain message").is_none());
+    }
+
+    #[test]
+    fn mismatched_shard_counts_fail() {
+        let mut fragments = split(PAYLOAD, 3, 1).unwrap();
+        let mut larger = split(PAYLOAD, 6, 2).unwrap().swap_remove(5);
+        larger.set = fragments[0].set.clone();
+        fragments.push(larger);
+        assert!(join(&fragments).is_err());
+    }
+
+    fn parse(header: &str) -> Result<Fragment> {
+        Fragment::parse(&format!("{}::AAAA", header)).unwrap()
+    }
+
+    #[test]
+    fn malformed_headers_fail() {
+        assert!(parse("pkf1;k=2;m=1;i=0;len=3;set=a").is_ok());
+        for header in [
+            "pkf1;k=18446744073709551615;m=1;i=0;len=3;set=a",
+            "pkf1;k=1;m=18446744073709551615;i=0;len=3;set=a",
+            "pkf1;k=4000000000;m=1;i=0;len=3;set=a",
+            "pkf1;k=200;m=57;i=0;len=3;set=a",
+            "pkf1;k=0;m=1;i=0;len=0;set=a",
+            "pkf1;k=2;m=0;i=0;len=3;set=a",
+            "pkf1;k=2;m=1;i=3;len=3;set=a",
+            "pkf1;k=2;m=1;i=0;len=7;set=a",
+            "pkf1;k=2;m=1;i=0;len=18446744073709551615;set=a",
+            "pkf1;k=2;m=1;i=0;set=a",
+            "pkf1;k=2;m=1;i=0;len=3;set=a;x=1",
+        ] {
+            assert!(parse(header).is_err(), "{}", header);
+        }
+    }
+
+    #[test]
+    fn mismatched_shard_lengths_fail() {
+        let mut fragments = split(PAYLOAD, 3, 1).unwrap();
+        fragments[1].shard.push(0);
+        assert!(join(&fragments).is_err());
+    }
+
+    #[test]
+    fn corrupted_extra_fragment_fails() {
+        let mut fragments = split(PAYLOAD, 4, 1).unwrap();
+        fragments[3].shard[0] ^= 1;
+        assert!(join(&fragments).is_err());
+    }
+}
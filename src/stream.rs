@@ -0,0 +1,120 @@
+use std::io::{self, Read, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::Result;
+use crate::verify::Segment;
+
+/// 复制块数据时的缓冲区大小
+pub const BUFFER_SIZE: usize = 64 * 1024;
+
+/// 流式改写的结果，只记录每个块的摘要
+pub struct Rewrite {
+    pub before: Vec<Segment>,
+    pub after: Vec<Segment>,
+    pub original_size: usize,
+    pub size: usize,
+}
+
+/// 尽量读满缓冲区，返回实际读取的字节数，文件结束时可能小于缓冲区大小
+pub fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// 读满缓冲区，文件提前结束时返回`message`
+pub fn read_exact_or<R: Read>(reader: &mut R, buf: &mut [u8], message: &str) -> Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => message.into(),
+        _ => e.into(),
+    })
+}
+
+/// 统计写入的字节数，用于计算输出中块的偏移
+pub struct Counter<W> {
+    inner: W,
+    pub count: usize,
+}
+
+impl<W: Write> Counter<W> {
+    pub fn new(inner: W) -> Counter<W> {
+        Counter { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 块的原始字节流经此处：计算摘要并写入输出，需要时保存块数据
+pub struct ChunkCopy<'a> {
+    digest: Sha256,
+    length: usize,
+    writer: &'a mut dyn Write,
+    data: Option<Vec<u8>>,
+}
+
+impl<'a> ChunkCopy<'a> {
+    pub fn new(writer: &'a mut dyn Write, keep_data: bool) -> ChunkCopy<'a> {
+        ChunkCopy {
+            digest: Sha256::new(),
+            length: 0,
+            writer,
+            data: keep_data.then(Vec::new),
+        }
+    }
+
+    /// 块头、长度、CRC等数据以外的部分
+    pub fn raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.digest.update(bytes);
+        self.length += bytes.len();
+        self.writer.write_all(bytes)
+    }
+
+    /// 块数据
+    pub fn data(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.raw(bytes)?;
+        if let Some(data) = &mut self.data {
+            data.extend_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    /// 从`reader`复制`length`字节的块数据
+    pub fn copy_data<R: Read>(&mut self, reader: &mut R, length: usize, buf: &mut [u8], message: &str) -> Result<()> {
+        let mut remaining = length;
+        while remaining > 0 {
+            let n = remaining.min(buf.len());
+            read_exact_or(reader, &mut buf[..n], message)?;
+            self.data(&buf[..n])?;
+            remaining -= n;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self, offset: usize, chunk_type: String) -> (Segment, Option<Vec<u8>>) {
+        let segment = Segment {
+            offset,
+            chunk_type,
+            length: self.length,
+            digest: self.digest.finalize().into(),
+        };
+        (segment, self.data)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::args::{StripArgs, StripCategory};
+
+/// 被删除或替换的块
+#[derive(Debug)]
+pub struct Stripped {
+    pub index: usize,
+    pub category: StripCategory,
+    /// 替换后的块数据，None表示删除
+    pub replacement: Option<Vec<u8>>,
+}
+
+/// 按保留/删除策略判断是否删除该类别：
+/// 指定--drop时只删除列出的类别，否则删除--keep以外的所有类别
+pub fn removes(args: &StripArgs, category: StripCategory) -> bool {
+    if !args.drop.is_empty() {
+        args.drop.contains(&category)
+    } else {
+        !args.keep.contains(&category)
+    }
+}
+
+/// TIFF数据中IFD0的方向标签（0x0112）
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| {
+        let bytes: [u8; 2] = tiff.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |pos: usize| {
+        let bytes: [u8; 4] = tiff.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+    (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(0x0112))
+        .and_then(|entry| u16_at(entry + 8))
+}
+
+/// 只包含方向标签的TIFF数据
+fn orientation_exif(orientation: u16) -> Vec<u8> {
+    let mut tiff = b"MM\x00\x2a".to_vec();
+    tiff.extend(8u32.to_be_bytes()); // IFD0偏移
+    tiff.extend(1u16.to_be_bytes()); // 标签数
+    tiff.extend(0x0112u16.to_be_bytes());
+    tiff.extend(3u16.to_be_bytes()); // SHORT
+    tiff.extend(1u32.to_be_bytes());
+    tiff.extend(orientation.to_be_bytes());
+    tiff.extend([0, 0]);
+    tiff.extend(0u32.to_be_bytes()); // 没有下一个IFD
+    tiff
+}
+
+/// 删除EXIF但保留方向时，返回只含方向标签的TIFF数据
+pub fn reduce_exif(args: &StripArgs, tiff: &[u8]) -> Option<Vec<u8>> {
+    if removes(args, StripCategory::Orientation) {
+        return None;
+    }
+    exif_orientation(tiff).map(orientation_exif)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn args(keep: &[StripCategory], drop: &[StripCategory]) -> StripArgs {
+        StripArgs { keep: keep.to_vec(), drop: drop.to_vec(), ..Default::default() }
+    }
+
+    #[test]
+    fn reduce_exif_keeps_only_orientation() {
+        let tiff = fixtures::exif(6);
+        for args in [args(&[StripCategory::Orientation], &[]), args(&[], &[StripCategory::Exif])] {
+            let reduced = reduce_exif(&args, &tiff).unwrap();
+            assert_eq!(reduced, orientation_exif(6));
+            assert_eq!(exif_orientation(&reduced), Some(6));
+        }
+        assert_eq!(reduce_exif(&args(&[], &[]), &tiff), None);
+        assert_eq!(reduce_exif(&args(&[], &[StripCategory::Exif, StripCategory::Orientation]), &tiff), None);
+    }
+
+    #[test]
+    fn reduce_exif_ignores_invalid_tiff() {
+        let args = args(&[StripCategory::Orientation], &[]);
+        assert_eq!(reduce_exif(&args, b"not tiff"), None);
+        assert_eq!(reduce_exif(&args, &fixtures::exif(6)[..20]), None);
+        assert_eq!(exif_orientation(&orientation_exif(3)), Some(3));
+    }
+}
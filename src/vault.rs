@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// 保险库格式的版本
+const VERSION: u32 = 1;
+
+/// 同一个块中保存的多个键值对，序列化为JSON后作为普通载荷加密写入
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Vault {
+    vault: u32,
+    entries: BTreeMap<String, String>,
+}
+
+impl Default for Vault {
+    fn default() -> Vault {
+        Vault { vault: VERSION, entries: BTreeMap::new() }
+    }
+}
+
+impl Vault {
+    /// 解析解密后的载荷，不是保险库时返回错误
+    pub fn parse(message: &str) -> Result<Vault> {
+        let vault: Vault = serde_json::from_str(message).map_err(|_| "Payload is not a vault")?;
+        if vault.vault != VERSION {
+            return Err(format!("Unsupported vault version {}", vault.vault).into());
+        }
+        Ok(vault)
+    }
+
+    pub fn to_message(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// 设置键值，已有的值被替换
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.entries.insert(key.to_owned(), value.to_owned());
+    }
+
+    pub fn delete(&mut self, key: &str) -> Result<()> {
+        self.entries.remove(key).map(|_| ()).ok_or_else(|| format!("Key {} not found in vault", key).into())
+    }
+
+    /// 按字典序排列的键
+    pub fn keys(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+}
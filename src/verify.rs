@@ -0,0 +1,226 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use sha2::{Digest, Sha256};
+
+use crate::Result;
+use crate::report::ChangeReport;
+use crate::strip::Stripped;
+
+/// 差异最多显示的行数
+const MAX_DIFF_LINES: usize = 10;
+
+
+/// 原文件与输出的全部字节，流式处理时没有
+pub type Files<'a> = (&'a [u8], &'a [u8]);
+
+/// 文件中的一个块
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub offset: usize,
+    /// 块类型，与命令行中的chunk_type对应
+    pub chunk_type: String,
+    /// 块在文件中占用的全部字节数
+    pub length: usize,
+    /// 块原始字节的SHA-256，流式处理时只保存摘要而不保存数据
+    pub digest: [u8; 32],
+}
+
+impl Segment {
+    pub fn new(offset: usize, chunk_type: String, bytes: &[u8]) -> Segment {
+        Segment {
+            offset,
+            chunk_type,
+            length: bytes.len(),
+            digest: Sha256::digest(bytes).into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("{} @ {:#010x} ({} bytes)", self.chunk_type, self.offset, self.length)
+    }
+
+    fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.length
+    }
+
+    /// 两个文件都在内存中时直接比较块的字节，流式处理时比较长度与SHA-256
+    fn same_bytes(&self, other: &Segment, files: Option<Files>) -> bool {
+        match files {
+            Some((before, after)) => matches!((before.get(self.range()), after.get(other.range())), (Some(old), Some(new)) if old == new),
+            None => self.length == other.length && self.digest == other.digest,
+        }
+    }
+}
+
+/// 去掉第一个指定类型的块，即载荷所在的块
+fn without_payload<'a>(segments: &'a [Segment], chunk_type: &str) -> Vec<&'a Segment> {
+    let index = segments.iter().position(|segment| segment.chunk_type == chunk_type);
+    segments
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != index)
+        .map(|(_, segment)| segment)
+        .collect()
+}
+
+/// 逐个比较块，`same_bytes`为false的位置只比较类型，不同时返回差异
+fn compare(before: &[&Segment], after: &[&Segment], files: Option<Files>, same_bytes: impl Fn(usize) -> bool, reason: &str) -> Result<()> {
+    let mut diff = Vec::new();
+    for index in 0..before.len().max(after.len()) {
+        let (old, new) = (before.get(index), after.get(index));
+        let unchanged = match (old, new) {
+            (Some(old), Some(new)) => old.chunk_type == new.chunk_type && (!same_bytes(index) || old.same_bytes(new, files)),
+            _ => false,
+        };
+        if unchanged {
+            continue;
+        }
+        if let Some(old) = old {
+            diff.push(format!("  - #{} {}", index, old.describe()));
+        }
+        if let Some(new) = new {
+            diff.push(format!("  + #{} {}", index, new.describe()));
+        }
+    }
+    if diff.is_empty() {
+        return Ok(());
+    }
+
+    let total = diff.len();
+    diff.truncate(MAX_DIFF_LINES);
+    if total > MAX_DIFF_LINES {
+        diff.push(format!("  ... ({} more lines)", total - MAX_DIFF_LINES));
+    }
+    Err(format!("Verification failed, {}:\n{}", reason, diff.join("\n")).into())
+}
+
+/// 检查除载荷所在块外，其余块与原文件逐字节相同，不同时返回差异
+pub fn verify_chunks(before: &[Segment], after: &[Segment], files: Option<Files>, chunk_type: &str) -> Result<()> {
+    let before = without_payload(before, chunk_type);
+    let after = without_payload(after, chunk_type);
+    compare(&before, &after, files, |_| true, "chunks other than the payload changed")
+}
+
+/// 检查strip只删除或替换了计划中的块，其余块逐字节相同
+pub fn verify_stripped(before: &[Segment], after: &[Segment], files: Files, stripped: &[Stripped]) -> Result<()> {
+    let removed = |index: usize| stripped.iter().any(|s| s.index == index && s.replacement.is_none());
+    let replaced = |index: usize| stripped.iter().any(|s| s.index == index && s.replacement.is_some());
+    let kept: Vec<usize> = (0..before.len()).filter(|&index| !removed(index)).collect();
+    let expected: Vec<&Segment> = kept.iter().map(|&index| &before[index]).collect();
+    let after: Vec<&Segment> = after.iter().collect();
+    compare(&expected, &after, Some(files), |i| !replaced(kept[i]), "chunks that should be kept changed")
+}
+
+/// 检查写入后的载荷可以原样读出
+pub fn verify_payload(written: &[u8], expected: &[u8]) -> Result<()> {
+    if written != expected {
+        return Err(format!(
+            "Verification failed, payload read back as {} bytes, expected {} bytes",
+            written.len(),
+            expected.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// 检查原地追加的结尾可以原样读回
+pub fn verify_appended(written: &[u8], expected: &[u8]) -> Result<()> {
+    if written != expected {
+        return Err(format!(
+            "Verification failed, appended bytes read back differently ({} bytes written, {} bytes read)",
+            expected.len(),
+            written.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// 第一个指定类型的块及该类型块的数量
+fn find<'a>(segments: &'a [Segment], chunk_type: &str) -> (Option<&'a Segment>, usize) {
+    let mut matches = segments.iter().filter(|segment| segment.chunk_type == chunk_type);
+    let first = matches.next();
+    (first, first.map_or(0, |_| 1 + matches.count()))
+}
+
+/// 载荷所在块的改动，按写入前后该类型块的数量判断
+pub fn changes(before: &[Segment], after: &[Segment], chunk_type: &str) -> Vec<ChangeReport> {
+    let (old, old_count) = find(before, chunk_type);
+    let (new, new_count) = find(after, chunk_type);
+    let (action, segment) = match (old_count.cmp(&new_count), old, new) {
+        (Ordering::Less, _, Some(new)) => ("added", new),
+        (Ordering::Equal, _, Some(new)) => ("replaced", new),
+        (Ordering::Greater, Some(old), _) => ("deleted", old),
+        _ => return Vec::new(),
+    };
+    vec![ChangeReport {
+        action,
+        chunk_type: segment.chunk_type.clone(),
+        offset: segment.offset,
+        length: segment.length,
+        category: None,
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::StripCategory;
+
+    /// 依次拼接块的字节，返回文件内容与各块
+    fn file(chunks: &[(&str, &[u8])]) -> (Vec<u8>, Vec<Segment>) {
+        let mut bytes = Vec::new();
+        let mut segments = Vec::new();
+        for (chunk_type, data) in chunks {
+            segments.push(Segment::new(bytes.len(), chunk_type.to_string(), data));
+            bytes.extend_from_slice(data);
+        }
+        (bytes, segments)
+    }
+
+    #[test]
+    fn payload_chunk_may_change() {
+        let (old, before) = file(&[("IHDR", b"aa"), ("ruSt", b"x"), ("IEND", b"bb")]);
+        let (new, after) = file(&[("IHDR", b"aa"), ("ruSt", b"yyy"), ("IEND", b"bb")]);
+        assert!(verify_chunks(&before, &after, Some((&old, &new)), "ruSt").is_ok());
+        assert!(verify_chunks(&before, &after, None, "ruSt").is_ok());
+
+        // 新增载荷块，但其他块不能多出或减少
+        let (empty, before) = file(&[("IHDR", b"aa"), ("IEND", b"bb")]);
+        assert!(verify_chunks(&before, &after, Some((&empty, &new)), "ruSt").is_ok());
+        assert!(verify_chunks(&before, &after, Some((&empty, &new)), "teXt").is_err());
+    }
+
+    #[test]
+    fn other_chunks_must_keep_their_bytes() {
+        let (old, before) = file(&[("IHDR", b"aa"), ("ruSt", b"x"), ("IEND", b"bb")]);
+        let (new, after) = file(&[("IHDR", b"ab"), ("ruSt", b"x"), ("IEND", b"bb")]);
+        assert!(verify_chunks(&before, &after, Some((&old, &new)), "ruSt").is_err());
+        assert!(verify_chunks(&before, &after, None, "ruSt").is_err());
+
+        // 在内存中时比较字节本身，而不是摘要
+        let mut forged = after.clone();
+        forged[0].digest = before[0].digest;
+        assert!(verify_chunks(&before, &forged, None, "ruSt").is_ok());
+        assert!(verify_chunks(&before, &forged, Some((&old, &new)), "ruSt").is_err());
+    }
+
+    #[test]
+    fn strip_only_touches_planned_chunks() {
+        let (old, before) = file(&[("IHDR", b"aa"), ("tEXt", b"comment"), ("eXIf", b"exif"), ("IEND", b"bb")]);
+        let stripped = [
+            Stripped { index: 1, category: StripCategory::Comment, replacement: None },
+            Stripped { index: 2, category: StripCategory::Exif, replacement: Some(b"o".to_vec()) },
+        ];
+        let (new, after) = file(&[("IHDR", b"aa"), ("eXIf", b"o"), ("IEND", b"bb")]);
+        assert!(verify_stripped(&before, &after, (&old, &new), &stripped).is_ok());
+
+        let (kept, after) = file(&[("IHDR", b"aa"), ("tEXt", b"comment"), ("eXIf", b"o"), ("IEND", b"bb")]);
+        assert!(verify_stripped(&before, &after, (&old, &kept), &stripped).is_err());
+
+        let (changed, after) = file(&[("IHDR", b"ab"), ("eXIf", b"o"), ("IEND", b"bb")]);
+        assert!(verify_stripped(&before, &after, (&old, &changed), &stripped).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::args::WriteArgs;
+use crate::Result;
+use crate::verify;
+
+/// 同一进程内临时文件的序号，批量处理时避免重名
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// 在目标文件所在目录创建临时文件，保证rename不跨文件系统
+fn create_temp(output: &Path) -> Result<(PathBuf, File)> {
+    let dir = output.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = output.file_name().ok_or("Invalid output path")?.to_string_lossy();
+    loop {
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!(".{}.{}.{}.tmp", name, std::process::id(), counter));
+        match OpenOptions::new().read(true).write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// 备份文件路径：在原文件名后加上.bak
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// 同步目录，使rename在崩溃后依然有效
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// 保留原文件的权限与修改时间，并同步到磁盘
+fn finish_temp(file: &File, source: &Path, options: &WriteArgs) -> Result<()> {
+    if let Ok(metadata) = fs::metadata(source) {
+        file.set_permissions(metadata.permissions())?;
+        if options.preserve_mtime {
+            file.set_modified(metadata.modified()?)?;
+        }
+    }
+    file.sync_all()?;
+    Ok(())
+}
+
+/// 写入标准输出
+pub fn write_stdout(data: &[u8]) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(data)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// 创建只有所有者可读写的新文件，已存在时返回错误，用于保存密钥
+pub fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// 原子写入：先写入同目录下的临时文件并同步到磁盘，再重命名覆盖目标文件。
+/// 写入过程中崩溃或磁盘已满时，目标文件保持原样。
+/// `source`为输入文件，用于保留权限与修改时间。
+pub fn write_atomic(source: &Path, output: &Path, data: &[u8], options: &WriteArgs) -> Result<()> {
+    write_atomic_with(source, output, options, |file| Ok(file.write_all(data)?))
+}
+
+/// 与`write_atomic`相同，但由`write`流式写入临时文件。
+/// `write`可以读回临时文件进行校验，返回错误时放弃写入。
+pub fn write_atomic_with<T, F>(source: &Path, output: &Path, options: &WriteArgs, write: F) -> Result<T>
+where
+    F: FnOnce(&mut File) -> Result<T>,
+{
+    if let Some(parent) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let (temp, mut file) = create_temp(output)?;
+    let result = write(&mut file).and_then(|value| {
+        finish_temp(&file, source, options)?;
+        Ok(value)
+    });
+    drop(file);
+    let value = match result {
+        Ok(value) => value,
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+    };
+
+    if options.backup && output.exists() {
+        let backup = backup_path(output);
+        if let Err(e) = fs::copy(output, &backup) {
+            let _ = fs::remove_file(&temp);
+            return Err(format!("Failed to back up {}: {}", output.display(), e).into());
+        }
+        File::open(&backup)?.sync_all()?;
+    }
+
+    if let Err(e) = fs::rename(&temp, output) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    sync_dir(output)?;
+    Ok(value)
+}
+
+/// 原地追加：文件中结尾标记之前的部分保持不变，只需写入新块与结尾标记
+pub struct Append {
+    /// 截断的位置，即结尾标记的偏移
+    pub offset: u64,
+    /// 新块与原来的结尾标记
+    pub tail: Vec<u8>,
+    /// 新块的字节数
+    pub length: usize,
+}
+
+/// 从`offset`处写入`tail`，只有文件长度与写入后的结尾不一致时才截断
+fn write_tail(file: &mut File, offset: u64, tail: &[u8]) -> Result<()> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(tail)?;
+    let end = offset + tail.len() as u64;
+    if file.metadata()?.len() != end {
+        file.set_len(end)?;
+    }
+    file.sync_data()?;
+    Ok(())
+}
+
+/// 读回写入的结尾进行校验
+fn verify_tail(file: &mut File, offset: u64, tail: &[u8]) -> Result<()> {
+    let mut written = Vec::with_capacity(tail.len());
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_to_end(&mut written)?;
+    verify::verify_appended(&written, tail)
+}
+
+/// 原地追加，不重写文件中已有的数据。写入后读回结尾并由`verify`重新解码载荷，
+/// 失败时恢复原来的结尾；与`write_atomic`不同，写入过程中断电可能留下不完整的文件
+pub fn append_in_place<F>(path: &Path, append: &Append, options: &WriteArgs, verify: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()>,
+{
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let modified = file.metadata()?.modified()?;
+    let mut original = Vec::new();
+    file.seek(SeekFrom::Start(append.offset))?;
+    file.read_to_end(&mut original)?;
+
+    let result = write_tail(&mut file, append.offset, &append.tail)
+        .and_then(|_| verify_tail(&mut file, append.offset, &append.tail))
+        .and_then(|_| verify());
+    if let Err(e) = result {
+        if let Err(restore) = write_tail(&mut file, append.offset, &original) {
+            return Err(format!("{}, and failed to restore {}: {}", e, path.display(), restore).into());
+        }
+        return Err(e);
+    }
+    if options.preserve_mtime {
+        file.set_modified(modified)?;
+    }
+    file.sync_all()?;
+    Ok(())
+}